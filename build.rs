@@ -2,6 +2,7 @@ use wesl::Wesl;
 
 fn main() {
 	let compiler = Wesl::new("src/shaders");
-	compiler.build_artifact(&"package::fragment".parse().unwrap(), "fragment");
+	compiler.build_artifact(&"package::trace".parse().unwrap(), "trace");
+	compiler.build_artifact(&"package::blit".parse().unwrap(), "blit");
 	compiler.build_artifact(&"package::vertex".parse().unwrap(), "vertex");
 }
@@ -1,8 +1,13 @@
-use crate::setup;
-use crate::state::State;
+use crate::camera::CameraParameters;
+use crate::display::DisplaySettings;
+use crate::gpu::{AdapterOptions, GpuError};
+use crate::screenshot::SceneSource;
+use crate::state::WindowRenderer;
+use crate::world::World;
 use std::sync::Arc;
 use std::time::Instant;
 use winit::application::ApplicationHandler;
+use winit::dpi::PhysicalSize;
 use winit::event::{DeviceEvent, DeviceId, ElementState, KeyEvent, MouseButton, WindowEvent};
 use winit::event_loop::ActiveEventLoop;
 use winit::keyboard::{KeyCode, PhysicalKey};
@@ -18,27 +23,97 @@ pub struct ControlMap {
 	pub move_backward: bool,
 	pub move_left: bool,
 	pub move_right: bool,
+	pub aperture_wider: bool,
+	pub aperture_narrower: bool,
+	pub focus_farther: bool,
+	pub focus_nearer: bool,
+	pub brighter: bool,
+	pub darker: bool,
+	// Cleared once handled
+	pub auto_focus: bool,
+	pub next_tone_mapper: bool,
+	pub screenshot: bool,
 }
 pub enum App {
-	Initializing,
+	Initializing {
+		world: Box<World>,
+		camera_parameters: CameraParameters,
+		display: DisplaySettings,
+		source: SceneSource,
+		window_size: Option<PhysicalSize<u32>>,
+		adapter_options: AdapterOptions,
+	},
 	Running {
-		state: State,
+		state: Box<WindowRenderer>,
 		control_map: ControlMap,
 		delta_time: f32,
 	},
+	// The window couldn't get a renderer, the event loop exits
+	Failed(GpuError),
 }
 impl App {
-	pub fn new() -> Self {
-		Self::Initializing
+	pub fn new(
+		world: World,
+		camera_parameters: CameraParameters,
+		display: DisplaySettings,
+		source: SceneSource,
+		window_size: Option<PhysicalSize<u32>>,
+		adapter_options: AdapterOptions,
+	) -> Self {
+		Self::Initializing {
+			world: Box::new(world),
+			camera_parameters,
+			display,
+			source,
+			window_size,
+			adapter_options,
+		}
+	}
+
+	// Why the event loop exited early, if it did
+	pub fn error(&self) -> Option<&GpuError> {
+		match self {
+			Self::Failed(e) => Some(e),
+			_ => None,
+		}
 	}
 }
 
 impl ApplicationHandler for App {
 	fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-		let window = Arc::new(event_loop.create_window(Window::default_attributes()).unwrap());
+		let Self::Initializing {
+			world,
+			camera_parameters,
+			display,
+			source,
+			window_size,
+			adapter_options,
+		} = self
+		else {
+			return;
+		};
 
-		let (world, camera_parameters) = setup();
-		let state = pollster::block_on(State::new(window.clone(), camera_parameters, &world));
+		let mut attributes = Window::default_attributes();
+		if let Some(size) = window_size {
+			attributes = attributes.with_inner_size(*size);
+		}
+		let window = Arc::new(event_loop.create_window(attributes).unwrap());
+
+		let state = match pollster::block_on(WindowRenderer::new(
+			window.clone(),
+			*camera_parameters,
+			*display,
+			source.clone(),
+			world,
+			adapter_options,
+		)) {
+			Ok(state) => Box::new(state),
+			Err(e) => {
+				*self = Self::Failed(e);
+				event_loop.exit();
+				return;
+			},
+		};
 
 		window.request_redraw();
 		*self = Self::Running {
@@ -107,6 +182,17 @@ impl ApplicationHandler for App {
 					KeyCode::KeyA => &mut control_map.move_left,
 					KeyCode::KeyD => &mut control_map.move_right,
 
+					KeyCode::BracketRight => &mut control_map.aperture_wider,
+					KeyCode::BracketLeft => &mut control_map.aperture_narrower,
+					KeyCode::Equal => &mut control_map.focus_farther,
+					KeyCode::Minus => &mut control_map.focus_nearer,
+					KeyCode::KeyF if pressed => &mut control_map.auto_focus,
+
+					KeyCode::Period => &mut control_map.brighter,
+					KeyCode::Comma => &mut control_map.darker,
+					KeyCode::KeyT if pressed => &mut control_map.next_tone_mapper,
+					KeyCode::F12 if pressed => &mut control_map.screenshot,
+
 					KeyCode::Escape => {
 						state.unfocus();
 						return;
@@ -124,7 +210,7 @@ impl ApplicationHandler for App {
 
 				let elapsed = start.elapsed();
 				*delta_time = elapsed.as_secs_f32();
-				println!("Frame time: {:?}", elapsed);
+				log::debug!("Frame time: {:?}", elapsed);
 			},
 			WindowEvent::Resized(size) => {
 				state.resize(size);
@@ -141,12 +227,9 @@ impl ApplicationHandler for App {
 			return;
 		}
 
-		match event {
-			DeviceEvent::MouseMotion { delta } => {
-				control_map.move_yaw += delta.0 as f32;
-				control_map.move_pitch += delta.1 as f32;
-			},
-			_ => {},
+		if let DeviceEvent::MouseMotion { delta } = event {
+			control_map.move_yaw += delta.0 as f32;
+			control_map.move_pitch += delta.1 as f32;
 		}
 	}
 }
@@ -0,0 +1,71 @@
+use crate::material::MaterialReference;
+use glam::{vec3, Vec3};
+
+// Must match the constants in background.wesl
+pub(crate) const SKY: u32 = 0;
+pub(crate) const SOLID: u32 = 1;
+pub(crate) const ENVIRONMENT: u32 = 2;
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub enum Background {
+	// White to light blue gradient from the book
+	#[default]
+	Sky,
+	Solid(Vec3),
+	// The map set with `World::set_environment_map`, turned by `rotation` radians around +y
+	Environment { rotation: f32, intensity: f32 },
+}
+impl Background {
+	pub fn uniform(&self, fog: Option<SurfaceFog>) -> BackgroundUniform {
+		let uniform = match *self {
+			Background::Sky => BackgroundUniform::new(SKY, vec3(0.5, 0.7, 1.0), 0.0, 1.0),
+			Background::Solid(color) => BackgroundUniform::new(SOLID, color, 0.0, 1.0),
+			Background::Environment { rotation, intensity } => {
+				BackgroundUniform::new(ENVIRONMENT, Vec3::ONE, rotation, intensity)
+			},
+		};
+		match fog {
+			Some(fog) => BackgroundUniform {
+				fog_density: fog.density,
+				fog_material: fog.material.id(),
+				..uniform
+			},
+			None => uniform,
+		}
+	}
+}
+
+// Homogeneous medium that only fills the space in front of surfaces. Rays that hit nothing and environment samples
+// leave it unattenuated, so the background itself isn't fogged. Fog reaching out to the background would block its
+// light completely and leave sky lit scenes dark.
+#[derive(Copy, Clone)]
+pub struct SurfaceFog {
+	pub density: f32,
+	// Usually isotropic
+	pub material: MaterialReference,
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct BackgroundUniform {
+	pub(crate) color: Vec3,
+	pub(crate) background_type: u32,
+	pub(crate) rotation: f32,
+	pub(crate) intensity: f32,
+	// 0 without fog
+	pub(crate) fog_density: f32,
+	// Plain id, structs in uniforms need 16 byte alignment
+	pub(crate) fog_material: u32,
+}
+impl BackgroundUniform {
+	fn new(background_type: u32, color: Vec3, rotation: f32, intensity: f32) -> Self {
+		Self {
+			color,
+			background_type,
+			rotation,
+			intensity,
+			fog_density: 0.0,
+			fog_material: 0,
+		}
+	}
+}
@@ -0,0 +1,292 @@
+use glam::Vec3;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Aabb {
+	pub min: Vec3,
+	pub max: Vec3,
+}
+impl Aabb {
+	pub const EMPTY: Self = Self {
+		min: Vec3::INFINITY,
+		max: Vec3::NEG_INFINITY,
+	};
+	pub fn new(min: Vec3, max: Vec3) -> Self {
+		Self { min, max }
+	}
+	pub fn union(self, other: Self) -> Self {
+		Self {
+			min: self.min.min(other.min),
+			max: self.max.max(other.max),
+		}
+	}
+	pub fn grow(self, point: Vec3) -> Self {
+		Self {
+			min: self.min.min(point),
+			max: self.max.max(point),
+		}
+	}
+	pub fn centroid(&self) -> Vec3 {
+		(self.min + self.max) * 0.5
+	}
+	pub fn surface_area(&self) -> f32 {
+		let extent = self.max - self.min;
+		if extent.min_element() < 0.0 {
+			return 0.0;
+		}
+		2.0 * (extent.x * extent.y + extent.y * extent.z + extent.z * extent.x)
+	}
+}
+
+// A leaf has `count > 0` and covers `count` primitives starting at `left_or_first`.
+// An interior node has `count == 0`, its children are `left_or_first` and `left_or_first + 1`.
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct BvhNode {
+	pub min: Vec3,
+	pub left_or_first: u32,
+	pub max: Vec3,
+	pub count: u32,
+}
+impl BvhNode {
+	fn new(bounds: Aabb) -> Self {
+		Self {
+			min: bounds.min,
+			left_or_first: 0,
+			max: bounds.max,
+			count: 0,
+		}
+	}
+}
+
+pub struct Bvh {
+	pub nodes: Vec<BvhNode>,
+	// Primitive order expected by the leaves, `indices[i]` is the original index of the i-th primitive
+	pub indices: Vec<u32>,
+}
+
+const BIN_COUNT: usize = 16;
+const MAX_LEAF_SIZE: u32 = 8;
+// Must stay below the traversal stack size in `bvh.wesl`
+const MAX_DEPTH: u32 = 30;
+// Cost of visiting a node relative to one primitive intersection
+const TRAVERSAL_COST: f32 = 1.0;
+
+impl Bvh {
+	pub fn build(bounds: &[Aabb]) -> Self {
+		let centroids = bounds.iter().map(Aabb::centroid).collect::<Vec<_>>();
+		let mut builder = Builder {
+			bounds,
+			centroids: &centroids,
+			indices: (0..u32::try_from(bounds.len()).unwrap()).collect(),
+			nodes: Vec::with_capacity(bounds.len().max(1) * 2),
+		};
+
+		// An empty world gets a single node with an inverted box, traversal checks for it since the slab test
+		// would let every ray through
+		builder.nodes.push(BvhNode::new(Aabb::EMPTY));
+		if !bounds.is_empty() {
+			builder.subdivide(0, 0, bounds.len() as u32, 0);
+		}
+
+		Self {
+			nodes: builder.nodes,
+			indices: builder.indices,
+		}
+	}
+}
+
+struct Builder<'a> {
+	bounds: &'a [Aabb],
+	centroids: &'a [Vec3],
+	indices: Vec<u32>,
+	nodes: Vec<BvhNode>,
+}
+impl Builder<'_> {
+	fn range(&self, first: u32, count: u32) -> &[u32] {
+		&self.indices[first as usize..(first + count) as usize]
+	}
+
+	fn subdivide(&mut self, node_index: usize, first: u32, count: u32, depth: u32) {
+		let (bounds, centroid_bounds) =
+			self.range(first, count)
+				.iter()
+				.fold((Aabb::EMPTY, Aabb::EMPTY), |(bounds, centroid_bounds), &i| {
+					(
+						bounds.union(self.bounds[i as usize]),
+						centroid_bounds.grow(self.centroids[i as usize]),
+					)
+				});
+		self.nodes[node_index] = BvhNode::new(bounds);
+
+		let make_leaf = |nodes: &mut Vec<BvhNode>| {
+			nodes[node_index].left_or_first = first;
+			nodes[node_index].count = count;
+		};
+
+		if count == 1 || depth >= MAX_DEPTH {
+			return make_leaf(&mut self.nodes);
+		}
+
+		let Some(split) = self.find_split(first, count, centroid_bounds) else {
+			return make_leaf(&mut self.nodes);
+		};
+
+		let leaf_cost = count as f32;
+		let split_cost = TRAVERSAL_COST + split.cost / bounds.surface_area().max(f32::MIN_POSITIVE);
+		if split_cost >= leaf_cost && count <= MAX_LEAF_SIZE {
+			return make_leaf(&mut self.nodes);
+		}
+
+		// Partition the range so primitives left of the split plane come first
+		let range = &mut self.indices[first as usize..(first + count) as usize];
+		let mut left_count = 0;
+		for i in 0..range.len() {
+			if split.bin_of(self.centroids[range[i] as usize], &centroid_bounds) < split.bin {
+				range.swap(i, left_count);
+				left_count += 1;
+			}
+		}
+		let left_count = left_count as u32;
+		if left_count == 0 || left_count == count {
+			return make_leaf(&mut self.nodes);
+		}
+
+		let left = self.nodes.len();
+		self.nodes.push(BvhNode::new(Aabb::EMPTY));
+		self.nodes.push(BvhNode::new(Aabb::EMPTY));
+		self.nodes[node_index].left_or_first = left as u32;
+
+		self.subdivide(left, first, left_count, depth + 1);
+		self.subdivide(left + 1, first + left_count, count - left_count, depth + 1);
+	}
+
+	// Binned surface area heuristic, returns the cheapest split over all three axes
+	fn find_split(&self, first: u32, count: u32, centroid_bounds: Aabb) -> Option<Split> {
+		let mut best: Option<Split> = None;
+
+		for axis in 0..3 {
+			let extent = centroid_bounds.max[axis] - centroid_bounds.min[axis];
+			if extent <= 0.0 {
+				continue;
+			}
+
+			let mut bins = [(Aabb::EMPTY, 0u32); BIN_COUNT];
+			let probe = Split {
+				axis,
+				bin: 0,
+				cost: 0.0,
+			};
+			for &i in self.range(first, count) {
+				let bin = probe.bin_of(self.centroids[i as usize], &centroid_bounds);
+				bins[bin].0 = bins[bin].0.union(self.bounds[i as usize]);
+				bins[bin].1 += 1;
+			}
+
+			// Sweep from the right to get the cost of every split position in one pass each way
+			let mut right_costs = [0.0; BIN_COUNT];
+			let (mut right_bounds, mut right_count) = (Aabb::EMPTY, 0);
+			for bin in (1..BIN_COUNT).rev() {
+				right_bounds = right_bounds.union(bins[bin].0);
+				right_count += bins[bin].1;
+				right_costs[bin] = right_bounds.surface_area() * right_count as f32;
+			}
+
+			let (mut left_bounds, mut left_count) = (Aabb::EMPTY, 0);
+			for bin in 1..BIN_COUNT {
+				left_bounds = left_bounds.union(bins[bin - 1].0);
+				left_count += bins[bin - 1].1;
+				let cost = left_bounds.surface_area() * left_count as f32 + right_costs[bin];
+				if left_count > 0 && left_count < count && best.is_none_or(|best| cost < best.cost) {
+					best = Some(Split { axis, bin, cost });
+				}
+			}
+		}
+
+		best
+	}
+}
+
+#[derive(Copy, Clone)]
+struct Split {
+	axis: usize,
+	// Primitives in bins below this one go to the left child
+	bin: usize,
+	cost: f32,
+}
+impl Split {
+	fn bin_of(&self, centroid: Vec3, centroid_bounds: &Aabb) -> usize {
+		let min = centroid_bounds.min[self.axis];
+		let extent = centroid_bounds.max[self.axis] - min;
+		let bin = ((centroid[self.axis] - min) / extent * BIN_COUNT as f32) as usize;
+		bin.min(BIN_COUNT - 1)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use glam::vec3;
+
+	fn cube(center: Vec3, half_size: f32) -> Aabb {
+		Aabb::new(center - half_size, center + half_size)
+	}
+
+	#[test]
+	fn empty() {
+		let bvh = Bvh::build(&[]);
+		assert!(bvh.indices.is_empty());
+		assert_eq!(bvh.nodes.len(), 1);
+		// Inverted, so traversal can tell it apart from a box containing everything
+		let root = bvh.nodes[0];
+		assert!(root.min.cmpgt(root.max).all());
+		assert_eq!(root.count, 0);
+	}
+
+	#[test]
+	fn single_primitive() {
+		let bounds = cube(vec3(1.0, 2.0, 3.0), 0.5);
+		let bvh = Bvh::build(&[bounds]);
+		assert_eq!(bvh.indices, [0]);
+		assert_eq!(bvh.nodes.len(), 1);
+		let root = bvh.nodes[0];
+		assert_eq!((root.left_or_first, root.count), (0, 1));
+		assert_eq!(Aabb::new(root.min, root.max), bounds);
+	}
+
+	#[test]
+	fn nodes_contain_their_primitives() {
+		let bounds = (0..500)
+			.map(|i| {
+				let i = i as f32;
+				cube(vec3((i * 0.37).sin() * 10.0, (i * 0.73).cos() * 10.0, i * 0.05), 0.1 + (i * 0.11).sin().abs())
+			})
+			.collect::<Vec<_>>();
+		let bvh = Bvh::build(&bounds);
+
+		let mut sorted = bvh.indices.clone();
+		sorted.sort_unstable();
+		assert!(sorted.iter().copied().eq(0..bounds.len() as u32), "every primitive is referenced once");
+
+		let contains = |node: &BvhNode, inner: Aabb| node.min.cmple(inner.min).all() && node.max.cmpge(inner.max).all();
+		let mut leaf_primitives = 0;
+		let mut stack = vec![0];
+		while let Some(index) = stack.pop() {
+			let node = &bvh.nodes[index];
+			if node.count > 0 {
+				let first = node.left_or_first as usize;
+				for &primitive in &bvh.indices[first..first + node.count as usize] {
+					assert!(contains(node, bounds[primitive as usize]), "leaf {index} misses primitive {primitive}");
+				}
+				leaf_primitives += node.count;
+			} else {
+				for child in [node.left_or_first as usize, node.left_or_first as usize + 1] {
+					let child_node = &bvh.nodes[child];
+					let child_bounds = Aabb::new(child_node.min, child_node.max);
+					assert!(contains(node, child_bounds), "node {index} misses child {child}");
+					stack.push(child);
+				}
+			}
+		}
+		assert_eq!(leaf_primitives, bounds.len() as u32);
+	}
+}
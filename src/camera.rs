@@ -1,6 +1,7 @@
 use glam::{vec3, Vec3};
 use wgpu::{BindingResource, Buffer, BufferAddress, BufferDescriptor, BufferUsages, Device, Queue};
 
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct CameraParameters {
 	pub samples_per_pixel: u32,
 	pub max_depth: u32,
@@ -8,11 +9,56 @@ pub struct CameraParameters {
 	pub location: Vec3,
 	pub pitch: f32,
 	pub yaw: f32,
+	// Degrees, apex angle of the cone of rays through each pixel, 0 is a pinhole camera
+	pub defocus_angle: f32,
+	// Distance from `location` to the plane in perfect focus
+	pub focus_dist: f32,
+	// Interval the ray times are drawn from, moving objects are at their start at time 0 and their end at time 1
+	pub shutter_open: f32,
+	pub shutter_close: f32,
+	// Seeds the per pixel random numbers, the same seed, scene and settings trace the same image on the same adapter
+	pub seed: u32,
 }
+impl CameraParameters {
+	pub fn look_at(&mut self, target: Vec3) {
+		// The view direction is `-make_look(pitch, yaw)`
+		let backward = self.location - target;
+		if backward.length_squared() == 0.0 {
+			return;
+		}
+		let backward = backward.normalize();
+		self.pitch = backward.y.asin();
+		self.yaw = backward.x.atan2(backward.z);
+	}
+}
+
+// Valid lens settings, shared by scene files and the command line so both report them the same way
+pub fn check_fov(fov: f32) -> Result<f32, String> {
+	if !(fov > 0.0 && fov < 180.0) {
+		return Err("fov must be between 0 and 180 degrees".to_owned());
+	}
+	Ok(fov)
+}
+pub fn check_defocus_angle(defocus_angle: f32) -> Result<f32, String> {
+	if !(0.0..MAX_DEFOCUS_ANGLE).contains(&defocus_angle) {
+		return Err("defocus_angle must be at least 0 and below 180 degrees".to_owned());
+	}
+	Ok(defocus_angle)
+}
+pub fn check_focus_dist(focus_dist: f32) -> Result<f32, String> {
+	if !(focus_dist > 0.0 && focus_dist.is_finite()) {
+		return Err("focus_dist must be positive".to_owned());
+	}
+	Ok(focus_dist)
+}
+// A cone of 180 degrees or more has no focus
+pub const MAX_DEFOCUS_ANGLE: f32 = 180.0;
+
 pub struct Camera {
 	pub parameters: CameraParameters,
 	pub width: u32,
 	pub height: u32,
+	pub frame: u32,
 	buffer: Buffer,
 }
 impl Camera {
@@ -21,6 +67,7 @@ impl Camera {
 			parameters,
 			width,
 			height,
+			frame: 0,
 			buffer: device.create_buffer(&BufferDescriptor {
 				label: Some("Camera Uniform Buffer"),
 				size: size_of::<CameraUniform>() as BufferAddress,
@@ -30,44 +77,7 @@ impl Camera {
 		}
 	}
 	pub fn update_buffer(&self, queue: &Queue) {
-		let camera_uniform = {
-			let p = &self.parameters;
-
-			let center = p.location;
-			let direction = make_look(p.pitch, p.yaw);
-
-			let focal_length = direction.length();
-			let theta = p.fov.to_radians();
-			let h = (theta / 2.0).tan();
-			let viewport_height = 2.0 * h * focal_length;
-			let viewport_width = viewport_height * (self.width as f32 / self.height as f32);
-
-			let up_vector = vec3(0.0, 1.0, 0.0);
-
-			let w = direction.normalize();
-			let u = up_vector.cross(w).normalize();
-			let v = w.cross(u);
-
-			let viewport_u = viewport_width * u;
-			let viewport_v = viewport_height * -v;
-
-			let pixel_delta_u = viewport_u / (self.width as f32);
-			let pixel_delta_v = viewport_v / (self.height as f32);
-
-			let viewport_upper_left = center - (focal_length * w) - viewport_u / 2.0 - viewport_v / 2.0;
-			let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
-
-			CameraUniform {
-				center,
-				samples_per_pixel: self.parameters.samples_per_pixel,
-				pixel00_loc,
-				max_depth: self.parameters.max_depth,
-				pixel_delta_u,
-				_p2: 0,
-				pixel_delta_v,
-				_p3: 0,
-			}
-		};
+		let camera_uniform = CameraUniform::new(&self.parameters, self.width, self.height, self.frame);
 		queue.write_buffer(&self.buffer, 0, bytemuck::bytes_of(&camera_uniform));
 	}
 	pub fn binding(&self) -> BindingResource<'_> {
@@ -77,14 +87,70 @@ impl Camera {
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct CameraUniform {
-	center: Vec3,
-	samples_per_pixel: u32,
-	pixel00_loc: Vec3,
-	max_depth: u32,
-	pixel_delta_u: Vec3,
-	_p2: u32,
-	pixel_delta_v: Vec3,
-	_p3: u32,
+	pub(crate) center: Vec3,
+	pub(crate) samples_per_pixel: u32,
+	pub(crate) pixel00_loc: Vec3,
+	pub(crate) max_depth: u32,
+	pub(crate) pixel_delta_u: Vec3,
+	pub(crate) frame: u32,
+	pub(crate) pixel_delta_v: Vec3,
+	pub(crate) width: u32,
+	pub(crate) defocus_disk_u: Vec3,
+	pub(crate) height: u32,
+	pub(crate) defocus_disk_v: Vec3,
+	pub(crate) shutter_open: f32,
+	pub(crate) shutter_close: f32,
+	pub(crate) seed: u32,
+	_p0: u32,
+	_p1: u32,
+}
+impl CameraUniform {
+	pub fn new(p: &CameraParameters, width: u32, height: u32, frame: u32) -> Self {
+		let center = p.location;
+		let direction = make_look(p.pitch, p.yaw);
+
+		// The viewport lies in the focus plane, so rays through it converge there from anywhere on the lens
+		let theta = p.fov.to_radians();
+		let h = (theta / 2.0).tan();
+		let viewport_height = 2.0 * h * p.focus_dist;
+		let viewport_width = viewport_height * (width as f32 / height as f32);
+
+		let up_vector = vec3(0.0, 1.0, 0.0);
+
+		let w = direction.normalize();
+		let u = up_vector.cross(w).normalize();
+		let v = w.cross(u);
+
+		let viewport_u = viewport_width * u;
+		let viewport_v = viewport_height * -v;
+
+		let pixel_delta_u = viewport_u / (width as f32);
+		let pixel_delta_v = viewport_v / (height as f32);
+
+		let viewport_upper_left = center - (p.focus_dist * w) - viewport_u / 2.0 - viewport_v / 2.0;
+		let pixel00_loc = viewport_upper_left + 0.5 * (pixel_delta_u + pixel_delta_v);
+
+		let defocus_radius = p.focus_dist * (p.defocus_angle.to_radians() / 2.0).tan();
+
+		Self {
+			center,
+			samples_per_pixel: p.samples_per_pixel,
+			pixel00_loc,
+			max_depth: p.max_depth,
+			pixel_delta_u,
+			frame,
+			pixel_delta_v,
+			width,
+			defocus_disk_u: u * defocus_radius,
+			height,
+			defocus_disk_v: v * defocus_radius,
+			shutter_open: p.shutter_open,
+			shutter_close: p.shutter_close,
+			seed: p.seed,
+			_p0: 0,
+			_p1: 0,
+		}
+	}
 }
 pub fn make_look(pitch: f32, yaw: f32) -> Vec3 {
 	vec3(yaw.sin() * pitch.cos(), pitch.sin(), yaw.cos() * pitch.cos())
@@ -0,0 +1,243 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use glam::Vec3;
+use rtiow_wgpu::camera::{check_defocus_angle, check_focus_dist, check_fov};
+use rtiow_wgpu::CameraParameters;
+use rtiow_wgpu::display::{DisplaySettings, ToneMapper};
+use rtiow_wgpu::gpu::AdapterOptions;
+use rtiow_wgpu::scenes::DEFAULT_SCENE;
+use std::path::PathBuf;
+use wgpu::{Backends, PowerPreference};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "GPU path tracer based on Ray Tracing in One Weekend")]
+pub struct Cli {
+	#[command(subcommand)]
+	pub command: Option<Command>,
+	#[command(flatten)]
+	pub scene: SceneArgs,
+	#[command(flatten)]
+	pub adapter: AdapterArgs,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+	/// Open a window and explore the scene (default)
+	Interactive,
+	/// Render the scene offscreen and write it to an image file
+	Render {
+		/// Output image, .png or .ppm
+		#[arg(short, long)]
+		output: PathBuf,
+		/// Number of accumulated passes of `--spp` samples each [default: 10]
+		#[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+		passes: Option<u32>,
+		/// Trace on the CPU instead of the GPU, used anyway when no adapter is found
+		#[arg(long)]
+		cpu: bool,
+	},
+}
+
+#[derive(Debug, Args)]
+pub struct SceneArgs {
+	/// Built-in scene name or path to a .toml scene file
+	#[arg(long, global = true, default_value = DEFAULT_SCENE)]
+	pub scene: String,
+	/// Image or initial window width in pixels
+	#[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+	pub width: Option<u32>,
+	/// Image or initial window height in pixels
+	#[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+	pub height: Option<u32>,
+	/// Samples per pixel traced each frame
+	#[arg(long, global = true, value_parser = clap::value_parser!(u32).range(1..))]
+	pub spp: Option<u32>,
+	/// Maximum number of bounces per path
+	#[arg(long, global = true)]
+	pub max_depth: Option<u32>,
+	/// Vertical field of view in degrees
+	#[arg(long, global = true, value_parser = parse_fov)]
+	pub fov: Option<f32>,
+	/// Camera position as `x,y,z`
+	#[arg(long, global = true, value_parser = parse_vec3, allow_hyphen_values = true)]
+	pub camera_pos: Option<Vec3>,
+	/// Point the camera looks at, as `x,y,z`
+	#[arg(long, global = true, value_parser = parse_vec3, allow_hyphen_values = true)]
+	pub look_at: Option<Vec3>,
+	/// Camera pitch in radians, ignored with `--look-at`
+	#[arg(long, global = true, allow_hyphen_values = true)]
+	pub pitch: Option<f32>,
+	/// Camera yaw in radians, ignored with `--look-at`
+	#[arg(long, global = true, allow_hyphen_values = true)]
+	pub yaw: Option<f32>,
+	/// Apex angle of the cone of rays through each pixel in degrees, 0 disables depth of field
+	#[arg(long, global = true, value_parser = parse_defocus_angle)]
+	pub defocus_angle: Option<f32>,
+	/// Distance from the camera to the plane in perfect focus
+	#[arg(long, global = true, value_parser = parse_focus_dist)]
+	pub focus_dist: Option<f32>,
+	/// Seed for the random scene generator and the per pixel random numbers, the same seed reproduces an image exactly
+	#[arg(long, global = true)]
+	pub seed: Option<u32>,
+	#[command(flatten)]
+	pub display: DisplayArgs,
+}
+impl SceneArgs {
+	pub fn apply(&self, parameters: &mut CameraParameters) {
+		if let Some(spp) = self.spp {
+			parameters.samples_per_pixel = spp;
+		}
+		if let Some(max_depth) = self.max_depth {
+			parameters.max_depth = max_depth;
+		}
+		if let Some(fov) = self.fov {
+			parameters.fov = fov;
+		}
+		if let Some(camera_pos) = self.camera_pos {
+			parameters.location = camera_pos;
+		}
+		if let Some(pitch) = self.pitch {
+			parameters.pitch = pitch;
+		}
+		if let Some(yaw) = self.yaw {
+			parameters.yaw = yaw;
+		}
+		if let Some(look_at) = self.look_at {
+			parameters.look_at(look_at);
+		}
+		if let Some(defocus_angle) = self.defocus_angle {
+			parameters.defocus_angle = defocus_angle;
+		}
+		if let Some(focus_dist) = self.focus_dist {
+			parameters.focus_dist = focus_dist;
+		}
+		if let Some(seed) = self.seed {
+			parameters.seed = seed;
+		}
+	}
+}
+
+#[derive(Debug, Args)]
+pub struct DisplayArgs {
+	/// Mapping of radiance to the displayable range [default: clamp]
+	#[arg(long, global = true, value_enum)]
+	pub tone_mapper: Option<ToneMapperArg>,
+	/// Exposure in stops, each one doubles the brightness
+	#[arg(long, global = true, allow_hyphen_values = true)]
+	pub exposure: Option<f32>,
+	/// Dither the 8-bit output to hide banding
+	#[arg(long, global = true)]
+	pub dither: bool,
+}
+impl DisplayArgs {
+	pub fn apply(&self, display: &mut DisplaySettings) {
+		if let Some(tone_mapper) = self.tone_mapper {
+			display.tone_mapper = match tone_mapper {
+				ToneMapperArg::Clamp => ToneMapper::Clamp,
+				ToneMapperArg::Reinhard => ToneMapper::Reinhard,
+				ToneMapperArg::Aces => ToneMapper::Aces,
+				ToneMapperArg::Agx => ToneMapper::Agx,
+			};
+		}
+		if let Some(exposure) = self.exposure {
+			display.exposure = exposure;
+		}
+		if self.dither {
+			display.dither = true;
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum ToneMapperArg {
+	Clamp,
+	Reinhard,
+	Aces,
+	Agx,
+}
+
+#[derive(Debug, Args)]
+pub struct AdapterArgs {
+	/// Graphics backends to consider: vulkan, gl, dx12 or metal, comma separated [default: all]
+	#[arg(long, global = true, env = "WGPU_BACKEND", value_parser = parse_backends)]
+	pub backend: Option<Backends>,
+	/// Use the first adapter whose name contains this string
+	#[arg(
+		long,
+		global = true,
+		env = "WGPU_ADAPTER_NAME",
+		conflicts_with_all = ["power_preference", "force_fallback_adapter"]
+	)]
+	pub adapter: Option<String>,
+	/// Preferred kind of adapter when no name is given
+	#[arg(long, global = true, env = "WGPU_POWER_PREF", value_enum, default_value_t = PowerPreferenceArg::None)]
+	pub power_preference: PowerPreferenceArg,
+	/// Force the fallback (software) adapter, e.g. llvmpipe or WARP
+	#[arg(long, global = true, env = "WGPU_FORCE_FALLBACK_ADAPTER")]
+	pub force_fallback_adapter: bool,
+}
+impl AdapterArgs {
+	pub fn options(&self) -> AdapterOptions {
+		AdapterOptions {
+			backends: self.backend,
+			name: self.adapter.clone(),
+			power_preference: match self.power_preference {
+				PowerPreferenceArg::None => PowerPreference::None,
+				PowerPreferenceArg::Low => PowerPreference::LowPower,
+				PowerPreferenceArg::High => PowerPreference::HighPerformance,
+			},
+			force_fallback_adapter: self.force_fallback_adapter,
+		}
+	}
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum PowerPreferenceArg {
+	None,
+	Low,
+	High,
+}
+
+fn parse_backends(s: &str) -> Result<Backends, String> {
+	let mut backends = Backends::empty();
+	for name in s.split(',') {
+		backends |= match name.trim().to_lowercase().as_str() {
+			"vulkan" | "vk" => Backends::VULKAN,
+			"gl" | "gles" | "opengl" => Backends::GL,
+			"dx12" | "d3d12" => Backends::DX12,
+			"metal" | "mtl" => Backends::METAL,
+			other => return Err(format!("unknown backend `{other}`, expected vulkan, gl, dx12 or metal")),
+		};
+	}
+	Ok(backends)
+}
+
+fn parse_fov(s: &str) -> Result<f32, String> {
+	check_fov(s.parse::<f32>().map_err(|e| e.to_string())?)
+}
+
+fn parse_defocus_angle(s: &str) -> Result<f32, String> {
+	check_defocus_angle(s.parse::<f32>().map_err(|e| e.to_string())?)
+}
+
+fn parse_focus_dist(s: &str) -> Result<f32, String> {
+	check_focus_dist(s.parse::<f32>().map_err(|e| e.to_string())?)
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+	let components = s
+		.split(',')
+		.map(|c| {
+			c.trim()
+				.parse::<f32>()
+				.map_err(|e| format!("invalid component `{c}`: {e}"))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	match components[..] {
+		[x, y, z] if !Vec3::new(x, y, z).is_finite() => Err("components must be finite".to_owned()),
+		[x, y, z] => Ok(Vec3::new(x, y, z)),
+		_ => Err(format!(
+			"expected 3 comma separated components, got {}",
+			components.len()
+		)),
+	}
+}
@@ -0,0 +1,165 @@
+// Reference path tracer on the CPU, a line by line port of the shaders. It renders the same images as `Renderer` up to
+// floating point differences, so it serves as ground truth for the GPU and as a fallback without an adapter.
+
+mod background;
+mod blit;
+mod light;
+mod material;
+mod rng;
+mod texture;
+mod tracing;
+
+use crate::background::BackgroundUniform;
+use crate::camera::{CameraParameters, CameraUniform};
+use crate::cpu::rng::Rng;
+use crate::cpu::texture::ImageArray;
+use crate::cpu::tracing::Ray;
+use crate::environment;
+use crate::image::Image;
+use crate::layout::SceneLayout;
+use crate::renderer::{RenderError, RenderSettings};
+use crate::world::World;
+use glam::{uvec2, vec2, vec3, Affine3A, Vec2, Vec3};
+use image::Rgba32FImage;
+use std::borrow::Cow;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::thread;
+
+// Everything the shaders read from their bindings
+struct Scene<'a> {
+	world: &'a World,
+	camera: CameraUniform,
+	layout: SceneLayout,
+	// Inverse of each instance's transform
+	world_to_object: Vec<Affine3A>,
+	background: BackgroundUniform,
+	images: ImageArray,
+	// Scenes without a map get a black texel, as on the GPU
+	environment_map: Cow<'a, Rgba32FImage>,
+	environment_distribution: Vec<f32>,
+}
+impl<'a> Scene<'a> {
+	fn new(world: &'a World, camera: CameraUniform) -> Self {
+		let layout = SceneLayout::new(world);
+		let world_to_object = layout.instances.iter().map(|instance| instance.transform().inverse()).collect();
+		let environment_map = match world.environment_map() {
+			Some(map) => Cow::Borrowed(map),
+			None => Cow::Owned(Rgba32FImage::new(1, 1)),
+		};
+		Self {
+			world,
+			camera,
+			layout,
+			world_to_object,
+			background: world.background().uniform(world.surface_fog()),
+			images: ImageArray::new(world.images()),
+			environment_distribution: environment::distribution(&environment_map),
+			environment_map,
+		}
+	}
+
+	// See `get_ray` in ray.wesl
+	fn get_ray(&self, position: Vec2, rng: &mut Rng) -> Ray {
+		let camera = &self.camera;
+		let offset = vec2(rng.float() - 0.5, rng.float() - 0.5);
+		let pixel_sample = camera.pixel00_loc
+			+ (position.x + offset.x) * camera.pixel_delta_u
+			+ (position.y + offset.y) * camera.pixel_delta_v;
+
+		// Thin lens, a zero radius disk degenerates to a pinhole
+		let lens = rng.in_unit_disk();
+		let origin = camera.center + lens.x * camera.defocus_disk_u + lens.y * camera.defocus_disk_v;
+		let time = camera.shutter_open + (camera.shutter_close - camera.shutter_open) * rng.float();
+		Ray::new(origin, pixel_sample - origin, time)
+	}
+
+	// Average radiance over `frames` frames of `samples_per_pixel` samples, seeded like `cs_main` in trace.wesl
+	fn trace_pixel(&self, x: u32, y: u32, frames: u32) -> Vec3 {
+		let position = vec2(x as f32, y as f32);
+		let mut sum = Vec3::ZERO;
+		for frame in 0..frames {
+			let mut rng = Rng::new(position, frame, self.camera.seed);
+			let mut color = Vec3::ZERO;
+			for _ in 0..self.camera.samples_per_pixel {
+				let ray = self.get_ray(position, &mut rng);
+				color += tracing::ray_color(self, &ray, &mut rng);
+			}
+			sum += color / self.camera.samples_per_pixel as f32;
+		}
+		sum / frames as f32
+	}
+}
+
+// Multi-threaded offscreen renderer with the same interface as `Renderer`
+pub struct CpuRenderer {
+	threads: usize,
+}
+
+impl CpuRenderer {
+	// One thread per core
+	pub fn new() -> Self {
+		Self::with_threads(thread::available_parallelism().map_or(1, NonZeroUsize::get))
+	}
+	pub fn with_threads(threads: usize) -> Self {
+		Self { threads: threads.max(1) }
+	}
+
+	// Blocks until the image is done
+	pub fn render_to_image(
+		&self,
+		world: &World,
+		camera_parameters: &CameraParameters,
+		settings: RenderSettings,
+	) -> Result<Image, RenderError> {
+		settings.validate(camera_parameters)?;
+		let RenderSettings {
+			width,
+			height,
+			samples,
+			display,
+		} = settings;
+
+		let frames = samples.div_ceil(camera_parameters.samples_per_pixel);
+		// The frame only seeds the random numbers, `trace_pixel` goes through all of them
+		let scene = Scene::new(world, CameraUniform::new(camera_parameters, width, height, 0));
+
+		let mut pixels = vec![0; width as usize * height as usize * 4];
+		// Rows are handed out one at a time so threads that get cheap rows pick up more
+		let rows = Mutex::new(pixels.chunks_exact_mut(width as usize * 4).enumerate());
+		thread::scope(|scope| {
+			for _ in 0..self.threads {
+				scope.spawn(|| {
+					loop {
+						let Some((y, row)) = rows.lock().unwrap().next() else {
+							break;
+						};
+						for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+							let (x, y) = (x as u32, y as u32);
+							let color = scene.trace_pixel(x, y, frames);
+							pixel.copy_from_slice(&blit::blit(display, color, uvec2(x, y)));
+						}
+					}
+				});
+			}
+		});
+
+		Ok(Image::new(width, height, pixels))
+	}
+}
+impl Default for CpuRenderer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+// Unit vectors completing the unit vector `n` to an orthonormal basis, see util.wesl
+fn tangents(n: Vec3) -> [Vec3; 2] {
+	let a = if n.x.abs() > 0.9 { vec3(0.0, 1.0, 0.0) } else { vec3(1.0, 0.0, 0.0) };
+	let t = n.cross(a).normalize();
+	[t, n.cross(t)]
+}
+
+fn near_zero(v: Vec3) -> bool {
+	v.abs().cmplt(Vec3::splat(f32::EPSILON)).all()
+}
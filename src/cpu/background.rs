@@ -0,0 +1,117 @@
+use super::rng::Rng;
+use super::tracing::Ray;
+use super::Scene;
+use crate::background::{ENVIRONMENT, SKY};
+use glam::{uvec2, vec2, vec3, UVec2, Vec3};
+use std::f32::consts::PI;
+
+pub fn background_color(scene: &Scene, ray: &Ray) -> Vec3 {
+	let background = &scene.background;
+	match background.background_type {
+		SKY => {
+			let unit_direction = ray.direction.normalize();
+			let a = 0.5 * (unit_direction.y + 1.0);
+			(1.0 - a) * Vec3::ONE + a * background.color
+		},
+		ENVIRONMENT => environment_radiance(scene, ray.direction.normalize()),
+		_ => background.color,
+	}
+}
+
+// Whether the background is a map that `sample_environment` can importance sample
+pub fn environment_sampled(scene: &Scene) -> bool {
+	scene.background.background_type == ENVIRONMENT
+}
+
+pub fn environment_radiance(scene: &Scene, direction: Vec3) -> Vec3 {
+	let texel = environment_texel(scene, direction);
+	let [r, g, b, _] = scene.environment_map.get_pixel(texel.x, texel.y).0;
+	vec3(r, g, b) * scene.background.intensity
+}
+
+// Picks a texel proportionally to its share of the luminance, then a direction uniformly within it. Returns the
+// normalized direction and its solid angle density.
+pub fn sample_environment(scene: &Scene, rng: &mut Rng) -> (Vec3, f32) {
+	let distribution = &scene.environment_distribution;
+	let (width, height) = scene.environment_map.dimensions();
+
+	let v_random = rng.float();
+	let row = find_interval(distribution, 0, height, v_random);
+	let row_start = distribution[row as usize];
+	let row_probability = distribution[row as usize + 1] - row_start;
+	let v = (row as f32 + (v_random - row_start) / row_probability) / height as f32;
+
+	let first = conditional_start(scene, row);
+	let u_random = rng.float();
+	let column = find_interval(distribution, first, width, u_random);
+	let column_start = distribution[(first + column) as usize];
+	let column_probability = distribution[(first + column) as usize + 1] - column_start;
+	let u = (column as f32 + (u_random - column_start) / column_probability) / width as f32;
+
+	// Inverse of the mapping in `environment_texel`
+	let theta = PI * v;
+	let phi = 2.0 * PI * u;
+	let sin_theta = theta.sin();
+	let direction = vec3(-sin_theta * phi.cos(), theta.cos(), sin_theta * phi.sin());
+
+	let pdf = solid_angle_pdf(row_probability * column_probability * (width * height) as f32, sin_theta);
+	(rotate_y(direction, scene.background.rotation), pdf)
+}
+
+// Density with which `sample_environment` picks the unit `direction`
+pub fn environment_pdf(scene: &Scene, direction: Vec3) -> f32 {
+	let distribution = &scene.environment_distribution;
+	let (width, height) = scene.environment_map.dimensions();
+	let texel = environment_texel(scene, direction);
+	let first = conditional_start(scene, texel.y) as usize;
+	let row = texel.y as usize;
+	let column = texel.x as usize;
+	let row_probability = distribution[row + 1] - distribution[row];
+	let column_probability = distribution[first + column + 1] - distribution[first + column];
+	// Rotating around +y leaves the polar angle unchanged
+	let sin_theta = (1.0 - direction.y * direction.y).max(0.0).sqrt();
+	solid_angle_pdf(row_probability * column_probability * (width * height) as f32, sin_theta)
+}
+
+// Texel of the environment map seen in the unit `direction`, with the same orientation as sphere UVs
+fn environment_texel(scene: &Scene, direction: Vec3) -> UVec2 {
+	let d = rotate_y(direction, -scene.background.rotation);
+	let (width, height) = scene.environment_map.dimensions();
+	let u = ((-d.z).atan2(d.x) + PI) / (2.0 * PI);
+	let v = d.y.clamp(-1.0, 1.0).acos() / PI;
+	(vec2(u, v) * vec2(width as f32, height as f32)).as_uvec2().min(uvec2(width - 1, height - 1))
+}
+
+// Converts a density over the unit square of texture coordinates to solid angle
+fn solid_angle_pdf(uv_pdf: f32, sin_theta: f32) -> f32 {
+	if sin_theta <= 0.0 {
+		return 0.0;
+	}
+	uv_pdf / (2.0 * PI * PI * sin_theta)
+}
+
+fn conditional_start(scene: &Scene, row: u32) -> u32 {
+	let (width, height) = scene.environment_map.dimensions();
+	height + 1 + row * (width + 1)
+}
+
+// Binary search for the interval of the CDF starting at `first` with `count` intervals that contains `value`
+fn find_interval(distribution: &[f32], first: u32, count: u32, value: f32) -> u32 {
+	let mut low = 0;
+	let mut high = count;
+	while low + 1 < high {
+		let middle = (low + high) / 2;
+		if distribution[(first + middle) as usize] <= value {
+			low = middle;
+		} else {
+			high = middle;
+		}
+	}
+	low
+}
+
+fn rotate_y(v: Vec3, angle: f32) -> Vec3 {
+	let c = angle.cos();
+	let s = angle.sin();
+	vec3(c * v.x + s * v.z, v.y, c * v.z - s * v.x)
+}
@@ -0,0 +1,90 @@
+use super::rng::hash;
+use crate::display::{DisplaySettings, ToneMapper};
+use glam::{Mat3, UVec2, Vec3};
+
+// See blit.wesl, column major
+const ACES_INPUT: Mat3 = Mat3::from_cols_array(&[
+	0.59719, 0.35458, 0.04823,
+	0.07600, 0.90834, 0.01566,
+	0.02840, 0.13383, 0.83777,
+]);
+const ACES_OUTPUT: Mat3 = Mat3::from_cols_array(&[
+	1.60475, -0.53108, -0.07367,
+	-0.10208, 1.10813, -0.00605,
+	-0.00327, -0.07276, 1.07602,
+]);
+// Digits as in blit.wesl
+#[allow(clippy::excessive_precision)]
+const AGX_INSET: Mat3 = Mat3::from_cols_array(&[
+	0.842479062253094, 0.0423282422610123, 0.0423756549057051,
+	0.0784335999999992, 0.878468636469772, 0.0784336,
+	0.0792237451477643, 0.0791661274605434, 0.879142973793104,
+]);
+#[allow(clippy::excessive_precision)]
+const AGX_OUTSET: Mat3 = Mat3::from_cols_array(&[
+	1.19687900512017, -0.0528968517574562, -0.0529716355144438,
+	-0.0980208811401368, 1.15190312990417, -0.0980434501171241,
+	-0.0990297440797205, -0.0989611768448433, 1.15107367264116,
+]);
+const AGX_MIN_EV: f32 = -12.47393;
+const AGX_MAX_EV: f32 = 4.026069;
+
+// The blit pass into an 8-bit sRGB target, without the crosshair
+pub fn blit(display: DisplaySettings, color: Vec3, pixel: UVec2) -> [u8; 4] {
+	let color = tone_map(display.tone_mapper, display.exposure.exp2() * color);
+	let mut encoded = linear_to_srgb(color);
+	if display.dither {
+		encoded = (encoded + triangular_noise(pixel) / 255.0).clamp(Vec3::ZERO, Vec3::ONE);
+	}
+	let [r, g, b] = encoded.to_array().map(|c| (c * 255.0).round() as u8);
+	[r, g, b, u8::MAX]
+}
+
+// Linear radiance to linear display values between 0 and 1
+fn tone_map(tone_mapper: ToneMapper, color: Vec3) -> Vec3 {
+	let c = color.max(Vec3::ZERO);
+	match tone_mapper {
+		ToneMapper::Reinhard => c / (1.0 + c),
+		ToneMapper::Aces => aces(c),
+		ToneMapper::Agx => agx(c),
+		ToneMapper::Clamp => c.min(Vec3::ONE),
+	}
+}
+
+fn aces(color: Vec3) -> Vec3 {
+	// The shader multiplies row vectors, which applies the transposed matrices
+	let v = ACES_INPUT.transpose() * color;
+	let a = v * (v + 0.0245786) - 0.000090537;
+	let b = v * (0.983729 * v + 0.432951) + 0.238081;
+	(ACES_OUTPUT.transpose() * (a / b)).clamp(Vec3::ZERO, Vec3::ONE)
+}
+
+fn agx(color: Vec3) -> Vec3 {
+	let inset = (AGX_INSET * color).max(Vec3::splat(1e-10));
+	let encoded = inset.map(f32::log2).clamp(Vec3::splat(AGX_MIN_EV), Vec3::splat(AGX_MAX_EV));
+	let x = (encoded - AGX_MIN_EV) / (AGX_MAX_EV - AGX_MIN_EV);
+
+	// Polynomial fit of the default contrast curve
+	let x2 = x * x;
+	let x4 = x2 * x2;
+	let curve = 15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232;
+
+	// The curve produces display encoded values for a 2.2 gamma display
+	(AGX_OUTSET * curve).clamp(Vec3::ZERO, Vec3::ONE).powf(2.2)
+}
+
+fn linear_to_srgb(color: Vec3) -> Vec3 {
+	color.map(|c| if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 })
+}
+
+// Between -1 and 1 with a triangular distribution, static per pixel
+fn triangular_noise(pixel: UVec2) -> Vec3 {
+	let mut state = hash(pixel.x ^ hash(pixel.y));
+	let mut noise = [0.0; 3];
+	for value in &mut noise {
+		let a = hash(state);
+		state = hash(a);
+		*value = (a & 0xFFFF) as f32 / 65536.0 + (state & 0xFFFF) as f32 / 65536.0 - 1.0;
+	}
+	Vec3::from_array(noise)
+}
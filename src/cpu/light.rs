@@ -0,0 +1,235 @@
+use super::background::{environment_pdf, environment_radiance, environment_sampled, sample_environment};
+use super::material::emitted;
+use super::rng::Rng;
+use super::tracing::{HitRecord, NO_PRIMITIVE};
+use super::{tangents, Scene};
+use crate::disk::Disk;
+use crate::layout::{QUAD, SPHERE, TRIANGLE};
+use crate::material::MaterialReference;
+use crate::mesh::Triangle;
+use crate::quad::Quad;
+use crate::sphere::Sphere;
+use glam::Vec3;
+use std::f32::consts::PI;
+
+pub struct LightSample {
+	// Normalized
+	pub direction: Vec3,
+	pub distance: f32,
+	pub radiance: Vec3,
+	// Solid angle density, including the choice of light
+	pub pdf: f32,
+}
+
+// Must match light.wesl
+pub const ENVIRONMENT_DISTANCE: f32 = 10000000.0;
+
+// Number of lights `sample_light` picks from, a sampled environment counts as one more
+fn light_count(scene: &Scene) -> usize {
+	scene.layout.lights.len() + environment_sampled(scene) as usize
+}
+
+// Picks a light uniformly and samples a direction towards it, moving lights are placed at `time`
+pub fn sample_light(scene: &Scene, point: Vec3, time: f32, rng: &mut Rng) -> Option<LightSample> {
+	let count = light_count(scene);
+	if count == 0 {
+		return None;
+	}
+	let choice = ((rng.float() * count as f32) as usize).min(count - 1);
+	let mut result = if choice == scene.layout.lights.len() {
+		sample_environment_light(scene, rng)?
+	} else {
+		let world = scene.world;
+		let primitive = scene.layout.primitives[scene.layout.lights[choice] as usize];
+		let index = primitive.index as usize;
+		match primitive.kind {
+			SPHERE => sample_sphere(scene, &world.spheres()[index], point, time, rng)?,
+			TRIANGLE => sample_triangle(scene, &world.triangles()[index], point, rng)?,
+			QUAD => sample_quad(scene, &world.quads()[index], point, rng)?,
+			_ => sample_disk(scene, &world.disks()[index], point, rng)?,
+		}
+	};
+	result.pdf /= count as f32;
+	Some(result)
+}
+
+// Density with which `sample_light` would pick the direction from `point` towards the emissive `record`
+pub fn light_pdf(scene: &Scene, point: Vec3, time: f32, record: &HitRecord) -> f32 {
+	if scene.layout.lights.is_empty() || record.primitive == NO_PRIMITIVE {
+		return 0.0;
+	}
+	let world = scene.world;
+	let primitive = scene.layout.primitives[record.primitive as usize];
+	let index = primitive.index as usize;
+	let pdf = match primitive.kind {
+		SPHERE => {
+			let sphere = &world.spheres()[index];
+			let extent = cone_extent(sphere.center + time * sphere.motion, sphere.radius, point);
+			if extent <= 0.0 {
+				return 0.0;
+			}
+			1.0 / (2.0 * PI * extent)
+		},
+		TRIANGLE => {
+			let triangle = &world.triangles()[index];
+			area_pdf(point, record.point, triangle_normal(scene, triangle), triangle_area(scene, triangle))
+		},
+		QUAD => {
+			let quad = &world.quads()[index];
+			let n = quad.u.cross(quad.v);
+			area_pdf(point, record.point, n.normalize(), n.length())
+		},
+		_ => {
+			let disk = &world.disks()[index];
+			area_pdf(point, record.point, disk.normal, PI * disk.radius * disk.radius)
+		},
+	};
+	pdf / light_count(scene) as f32
+}
+
+// Density with which `sample_light` would pick the unit `direction` towards the environment
+pub fn environment_light_pdf(scene: &Scene, direction: Vec3) -> f32 {
+	if !environment_sampled(scene) {
+		return 0.0;
+	}
+	environment_pdf(scene, direction) / light_count(scene) as f32
+}
+
+// Converts the density of sampling `light_point` uniformly on an area to solid angle as seen from `point`
+fn area_pdf(point: Vec3, light_point: Vec3, normal: Vec3, area: f32) -> f32 {
+	let to_light = light_point - point;
+	let distance_squared = to_light.dot(to_light);
+	let cosine = normal.dot(to_light).abs() / distance_squared.sqrt();
+	if cosine <= 0.0 || area <= 0.0 {
+		return 0.0;
+	}
+	distance_squared / (cosine * area)
+}
+
+// `1 - cos(theta_max)` of the cone the sphere subtends from `point`, or 0 if `point` is inside
+fn cone_extent(center: Vec3, radius: f32, point: Vec3) -> f32 {
+	let to_center = center - point;
+	let distance_squared = to_center.dot(to_center);
+	let radius_squared = radius * radius;
+	if distance_squared <= radius_squared {
+		return 0.0;
+	}
+	// Equal to `1 - sqrt(1 - x)` without cancellation for small or distant spheres
+	let x = radius_squared / distance_squared;
+	x / (1.0 + (1.0 - x).sqrt())
+}
+
+// Uniform in the cone the sphere subtends
+fn sample_sphere(scene: &Scene, sphere: &Sphere, point: Vec3, time: f32, rng: &mut Rng) -> Option<LightSample> {
+	let center = sphere.center + time * sphere.motion;
+	let extent = cone_extent(center, sphere.radius, point);
+	if extent <= 0.0 {
+		return None;
+	}
+
+	let to_center = center - point;
+	let distance_squared = to_center.dot(to_center);
+	let w = to_center / distance_squared.sqrt();
+	let [tangent, bitangent] = tangents(w);
+
+	let cos_theta = 1.0 - rng.float() * extent;
+	let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+	let phi = 2.0 * PI * rng.float();
+	let direction = ((tangent * phi.cos() + bitangent * phi.sin()) * sin_theta + w * cos_theta).normalize();
+
+	// Nearest intersection with the sphere, the direction lies inside the cone so it always hits
+	let b = direction.dot(to_center);
+	let c = distance_squared - sphere.radius * sphere.radius;
+	let distance = b - (b * b - c).max(0.0).sqrt();
+
+	Some(LightSample {
+		direction,
+		distance,
+		radiance: emitted(scene, sphere.material),
+		pdf: 1.0 / (2.0 * PI * extent),
+	})
+}
+
+fn sample_environment_light(scene: &Scene, rng: &mut Rng) -> Option<LightSample> {
+	let (direction, pdf) = sample_environment(scene, rng);
+	if pdf <= 0.0 {
+		return None;
+	}
+	Some(LightSample {
+		direction,
+		distance: ENVIRONMENT_DISTANCE,
+		radiance: environment_radiance(scene, direction),
+		pdf,
+	})
+}
+
+// Uniform over the triangle's area
+fn sample_triangle(scene: &Scene, triangle: &Triangle, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+	let [p0, p1, p2] = triangle.vertices.map(|i| scene.world.vertices()[i as usize].position);
+
+	let r1 = rng.float().sqrt();
+	let r2 = rng.float();
+	let light_point = (1.0 - r1) * p0 + r1 * (1.0 - r2) * p1 + r1 * r2 * p2;
+
+	let normal = triangle_normal(scene, triangle);
+	sample_area(scene, point, light_point, normal, triangle_area(scene, triangle), triangle.material)
+}
+
+// Uniform over the quad's area
+fn sample_quad(scene: &Scene, quad: &Quad, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+	let light_point = quad.q + rng.float() * quad.u + rng.float() * quad.v;
+	let n = quad.u.cross(quad.v);
+	sample_area(scene, point, light_point, n.normalize(), n.length(), quad.material)
+}
+
+// Uniform over the disk's area
+fn sample_disk(scene: &Scene, disk: &Disk, point: Vec3, rng: &mut Rng) -> Option<LightSample> {
+	let radius = disk.radius * rng.float().sqrt();
+	let phi = 2.0 * PI * rng.float();
+	let [tangent, bitangent] = tangents(disk.normal);
+	let light_point = disk.center + (tangent * phi.cos() + bitangent * phi.sin()) * radius;
+	let area = PI * disk.radius * disk.radius;
+	sample_area(scene, point, light_point, disk.normal, area, disk.material)
+}
+
+fn sample_area(
+	scene: &Scene,
+	point: Vec3,
+	light_point: Vec3,
+	normal: Vec3,
+	area: f32,
+	material: MaterialReference,
+) -> Option<LightSample> {
+	let to_light = light_point - point;
+	let distance = to_light.length();
+	let pdf = area_pdf(point, light_point, normal, area);
+	if distance <= 0.0 || pdf <= 0.0 {
+		return None;
+	}
+
+	Some(LightSample {
+		direction: to_light / distance,
+		distance,
+		radiance: emitted(scene, material),
+		pdf,
+	})
+}
+
+fn triangle_normal(scene: &Scene, triangle: &Triangle) -> Vec3 {
+	let [p0, p1, p2] = triangle.vertices.map(|i| scene.world.vertices()[i as usize].position);
+	(p1 - p0).cross(p2 - p0).normalize()
+}
+
+fn triangle_area(scene: &Scene, triangle: &Triangle) -> f32 {
+	let [p0, p1, p2] = triangle.vertices.map(|i| scene.world.vertices()[i as usize].position);
+	0.5 * (p1 - p0).cross(p2 - p0).length()
+}
+
+pub fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+	let a = pdf * pdf;
+	let b = other_pdf * other_pdf;
+	if a + b <= 0.0 {
+		return 0.0;
+	}
+	a / (a + b)
+}
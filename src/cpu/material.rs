@@ -0,0 +1,346 @@
+use super::rng::Rng;
+use super::texture::texture_value;
+use super::tracing::{HitRecord, Ray};
+use super::{near_zero, tangents, Scene};
+use crate::material::{Material, MaterialReference};
+use crate::texture::NO_TEXTURE;
+use glam::{vec3, Vec3};
+use std::f32::consts::PI;
+
+// Parameters of the microfacet material, see `Material::microfacet`
+struct Microfacet {
+	base_color: Vec3,
+	metallic: f32,
+	// GGX alpha, the square of the perceptual roughness
+	alpha: f32,
+	// Specular reflectance at normal incidence
+	f0: Vec3,
+	clearcoat: f32,
+	clearcoat_alpha: f32,
+}
+
+pub struct ScatterResult {
+	// Delta distributions can't be evaluated for light samples, see `evaluate`
+	pub specular: bool,
+	pub ray: Ray,
+	pub color: Vec3,
+}
+
+fn material<'a>(scene: &Scene<'a>, material_reference: MaterialReference) -> &'a Material {
+	&scene.world.materials()[material_reference.id() as usize]
+}
+
+// None if the path ends here
+pub fn scatter(
+	scene: &Scene,
+	material_reference: MaterialReference,
+	ray: &Ray,
+	hit_record: &HitRecord,
+	rng: &mut Rng,
+) -> Option<ScatterResult> {
+	let material = material(scene, material_reference);
+
+	match material.material_type {
+		// Lambertian
+		0 => {
+			let albedo = material_albedo(scene, material, hit_record);
+
+			let mut scatter_direction = hit_record.normal + rng.unit_vector();
+			if near_zero(scatter_direction) {
+				scatter_direction = hit_record.normal;
+			}
+
+			Some(ScatterResult {
+				specular: false,
+				ray: Ray::new(hit_record.point, scatter_direction, ray.time),
+				color: albedo,
+			})
+		},
+		// Metal
+		1 => {
+			let albedo = material_albedo(scene, material, hit_record);
+			let fuzz = material.data.w;
+
+			let reflected = ray.direction.reflect(hit_record.normal).normalize() + fuzz * rng.unit_vector();
+			Some(ScatterResult {
+				specular: true,
+				ray: Ray::new(hit_record.point, reflected, ray.time),
+				color: albedo,
+			})
+		},
+		// Dielectric
+		2 => {
+			let refraction_index = material.data.x;
+
+			let ri = if hit_record.front_face {
+				1.0 / refraction_index
+			} else {
+				refraction_index
+			};
+
+			let unit_direction = ray.direction.normalize();
+			let cos_theta = (-unit_direction).dot(hit_record.normal).min(1.0);
+			let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+
+			let direction = if ri * sin_theta > 1.0 || reflectance(cos_theta, ri) > rng.float() {
+				unit_direction.reflect(hit_record.normal)
+			} else {
+				refract(unit_direction, hit_record.normal, ri)
+			};
+
+			Some(ScatterResult {
+				specular: true,
+				ray: Ray::new(hit_record.point, direction, ray.time),
+				color: Vec3::ONE,
+			})
+		},
+		// Emissive
+		3 => None,
+		// Isotropic
+		4 => Some(ScatterResult {
+			specular: false,
+			ray: Ray::new(hit_record.point, rng.unit_vector(), ray.time),
+			color: material_albedo(scene, material, hit_record),
+		}),
+		// Microfacet
+		5 => {
+			let microfacet = microfacet_parameters(scene, material, hit_record);
+			let outgoing = -ray.direction.normalize();
+			let direction = sample_microfacet(&microfacet, hit_record.normal, outgoing, rng);
+			let pdf = microfacet_pdf(&microfacet, hit_record.normal, outgoing, direction);
+			let cosine = hit_record.normal.dot(direction);
+			if pdf <= 0.0 || cosine <= 0.0 {
+				return None;
+			}
+			Some(ScatterResult {
+				specular: false,
+				ray: Ray::new(hit_record.point, direction, ray.time),
+				color: microfacet_bsdf(&microfacet, hit_record.normal, outgoing, direction) * cosine / pdf,
+			})
+		},
+		_ => Some(ScatterResult {
+			specular: true,
+			ray: *ray,
+			color: vec3(1.0, 0.0, 1.0),
+		}),
+	}
+}
+
+// BSDF times cosine for light arriving from `direction` and leaving towards the unit `outgoing`, zero for specular
+// materials
+pub fn evaluate(
+	scene: &Scene,
+	material_reference: MaterialReference,
+	hit_record: &HitRecord,
+	outgoing: Vec3,
+	direction: Vec3,
+) -> Vec3 {
+	let material = material(scene, material_reference);
+
+	match material.material_type {
+		// Lambertian and isotropic
+		0 | 4 => {
+			material_albedo(scene, material, hit_record)
+				* scatter_pdf(scene, material_reference, hit_record, outgoing, direction)
+		},
+		// Microfacet
+		5 => {
+			let microfacet = microfacet_parameters(scene, material, hit_record);
+			let cosine = hit_record.normal.dot(direction.normalize()).max(0.0);
+			microfacet_bsdf(&microfacet, hit_record.normal, outgoing, direction.normalize()) * cosine
+		},
+		_ => Vec3::ZERO,
+	}
+}
+
+// Solid angle density with which `scatter` picks `direction` for a ray leaving towards the unit `outgoing`, zero for
+// specular materials
+pub fn scatter_pdf(
+	scene: &Scene,
+	material_reference: MaterialReference,
+	hit_record: &HitRecord,
+	outgoing: Vec3,
+	direction: Vec3,
+) -> f32 {
+	let material = material(scene, material_reference);
+
+	match material.material_type {
+		// Lambertian
+		0 => hit_record.normal.dot(direction.normalize()).max(0.0) / PI,
+		// Isotropic
+		4 => 1.0 / (4.0 * PI),
+		// Microfacet
+		5 => {
+			let microfacet = microfacet_parameters(scene, material, hit_record);
+			microfacet_pdf(&microfacet, hit_record.normal, outgoing, direction.normalize())
+		},
+		_ => 0.0,
+	}
+}
+
+pub fn emitted(scene: &Scene, material_reference: MaterialReference) -> Vec3 {
+	let material = material(scene, material_reference);
+
+	match material.material_type {
+		// Emissive
+		3 => {
+			let color = material.data.truncate();
+			let intensity = material.data.w;
+			color * intensity
+		},
+		_ => Vec3::ZERO,
+	}
+}
+
+fn material_albedo(scene: &Scene, material: &Material, hit_record: &HitRecord) -> Vec3 {
+	if material.texture == NO_TEXTURE {
+		return material.data.truncate();
+	}
+	texture_value(scene, material.texture, hit_record.uv, hit_record.point)
+}
+
+fn microfacet_parameters(scene: &Scene, material: &Material, hit_record: &HitRecord) -> Microfacet {
+	let base_color = material_albedo(scene, material, hit_record);
+	let metallic = material.data.w;
+	let roughness = material.parameters.x.max(0.03);
+	let clearcoat_roughness = material.parameters.w.max(0.03);
+	Microfacet {
+		base_color,
+		metallic,
+		alpha: roughness * roughness,
+		// Specular 0.5 is the 4% reflectance of common dielectrics
+		f0: Vec3::splat(0.08 * material.parameters.y).lerp(base_color, metallic),
+		clearcoat: material.parameters.z,
+		clearcoat_alpha: clearcoat_roughness * clearcoat_roughness,
+	}
+}
+
+// Probabilities of sampling the diffuse, specular and clearcoat lobes
+fn microfacet_lobes(microfacet: &Microfacet) -> Vec3 {
+	let weights = vec3(1.0 - microfacet.metallic, 1.0, 0.5 * microfacet.clearcoat);
+	weights / (weights.x + weights.y + weights.z)
+}
+
+// Unit `outgoing` and `direction`, both on the side of the normal
+fn microfacet_bsdf(microfacet: &Microfacet, normal: Vec3, outgoing: Vec3, direction: Vec3) -> Vec3 {
+	let n_dot_o = normal.dot(outgoing);
+	let n_dot_i = normal.dot(direction);
+	if n_dot_o <= 0.0 || n_dot_i <= 0.0 {
+		return Vec3::ZERO;
+	}
+	let half_vector = (outgoing + direction).normalize();
+	let n_dot_h = normal.dot(half_vector);
+	let o_dot_h = outgoing.dot(half_vector);
+
+	let fresnel = schlick(microfacet.f0, o_dot_h);
+	// Light the specular layer reflects doesn't reach the diffuse base
+	let diffuse = (1.0 - microfacet.metallic) * (Vec3::ONE - fresnel) * microfacet.base_color / PI;
+	let specular = fresnel * ggx(n_dot_o, n_dot_i, n_dot_h, microfacet.alpha);
+
+	// The coat is a dielectric with 4% reflectance, what it reflects doesn't reach the layers below
+	let coat = microfacet.clearcoat * schlick(Vec3::splat(0.04), o_dot_h).x;
+	let clearcoat = coat * ggx(n_dot_o, n_dot_i, n_dot_h, microfacet.clearcoat_alpha);
+	(diffuse + specular) * (1.0 - coat) + Vec3::splat(clearcoat)
+}
+
+// Picks one lobe, cosine weighted for the diffuse base and from the visible normals for the specular layers
+fn sample_microfacet(microfacet: &Microfacet, normal: Vec3, outgoing: Vec3, rng: &mut Rng) -> Vec3 {
+	let lobes = microfacet_lobes(microfacet);
+	let choice = rng.float();
+	if choice < lobes.x {
+		let direction = normal + rng.unit_vector();
+		if near_zero(direction) {
+			return normal;
+		}
+		return direction.normalize();
+	}
+
+	let alpha = if choice < lobes.x + lobes.y {
+		microfacet.alpha
+	} else {
+		microfacet.clearcoat_alpha
+	};
+	let [tangent, bitangent] = tangents(normal);
+	let local_outgoing = vec3(outgoing.dot(tangent), outgoing.dot(bitangent), outgoing.dot(normal));
+	let local_half_vector = sample_visible_normal(local_outgoing, alpha, rng);
+	let half_vector = tangent * local_half_vector.x + bitangent * local_half_vector.y + normal * local_half_vector.z;
+	(-outgoing).reflect(half_vector)
+}
+
+// Density of `sample_microfacet`, the mixture of the densities of all lobes
+fn microfacet_pdf(microfacet: &Microfacet, normal: Vec3, outgoing: Vec3, direction: Vec3) -> f32 {
+	let n_dot_o = normal.dot(outgoing);
+	let n_dot_i = normal.dot(direction);
+	if n_dot_o <= 0.0 || n_dot_i <= 0.0 {
+		return 0.0;
+	}
+	let n_dot_h = normal.dot((outgoing + direction).normalize());
+
+	let lobes = microfacet_lobes(microfacet);
+	lobes.x * n_dot_i / PI
+		+ lobes.y * visible_normal_pdf(n_dot_o, n_dot_h, microfacet.alpha)
+		+ lobes.z * visible_normal_pdf(n_dot_o, n_dot_h, microfacet.clearcoat_alpha)
+}
+
+// Trowbridge-Reitz normal distribution
+fn ggx_distribution(n_dot_h: f32, alpha: f32) -> f32 {
+	let a2 = alpha * alpha;
+	let d = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+	a2 / (PI * d * d)
+}
+
+// Smith masking of one direction
+fn smith_g1(n_dot_v: f32, alpha: f32) -> f32 {
+	let a2 = alpha * alpha;
+	2.0 * n_dot_v / (n_dot_v + (a2 + (1.0 - a2) * n_dot_v * n_dot_v).sqrt())
+}
+
+// Specular microfacet BRDF without the Fresnel term
+fn ggx(n_dot_o: f32, n_dot_i: f32, n_dot_h: f32, alpha: f32) -> f32 {
+	ggx_distribution(n_dot_h, alpha) * smith_g1(n_dot_o, alpha) * smith_g1(n_dot_i, alpha) / (4.0 * n_dot_o * n_dot_i)
+}
+
+// Density of reflecting about a half vector from `sample_visible_normal`, per solid angle of the reflected direction
+fn visible_normal_pdf(n_dot_o: f32, n_dot_h: f32, alpha: f32) -> f32 {
+	ggx_distribution(n_dot_h, alpha) * smith_g1(n_dot_o, alpha) / (4.0 * n_dot_o)
+}
+
+// Heitz's visible normal sampling, see material.wesl
+fn sample_visible_normal(outgoing: Vec3, alpha: f32, rng: &mut Rng) -> Vec3 {
+	// Stretch to the hemisphere configuration
+	let v = vec3(alpha * outgoing.x, alpha * outgoing.y, outgoing.z).normalize();
+	let length_squared = v.x * v.x + v.y * v.y;
+	let t1 = if length_squared > 0.0 {
+		vec3(-v.y, v.x, 0.0) / length_squared.sqrt()
+	} else {
+		Vec3::X
+	};
+	let t2 = v.cross(t1);
+
+	let r = rng.float().sqrt();
+	let phi = 2.0 * PI * rng.float();
+	let p1 = r * phi.cos();
+	let s = 0.5 * (1.0 + v.z);
+	let p2 = (1.0 - p1 * p1).sqrt() * (1.0 - s) + r * phi.sin() * s;
+	let normal = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * v;
+
+	// Unstretch
+	vec3(alpha * normal.x, alpha * normal.y, normal.z.max(1e-6)).normalize()
+}
+
+fn schlick(f0: Vec3, cosine: f32) -> Vec3 {
+	f0 + (Vec3::ONE - f0) * (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+}
+
+fn refract(uv: Vec3, n: Vec3, etai_over_etat: f32) -> Vec3 {
+	let cos_theta = (-uv).dot(n).min(1.0);
+	let r_out_perp = etai_over_etat * (uv + cos_theta * n);
+	let r_out_parallel = -(1.0 - r_out_perp.dot(r_out_perp)).abs().sqrt() * n;
+	r_out_perp + r_out_parallel
+}
+
+fn reflectance(cosine: f32, refraction_index: f32) -> f32 {
+	let r0 = (1.0 - refraction_index) / (1.0 + refraction_index);
+	let r0 = r0 * r0;
+	r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
+}
@@ -0,0 +1,55 @@
+use glam::{vec2, vec3, Vec2, Vec3};
+use std::f32::consts::PI;
+
+const SIGN_EXP: u32 = 0x3F800000;
+
+// See rng.wesl, every pixel and frame gets the same sequence as on the GPU
+pub struct Rng {
+	state: u32,
+}
+impl Rng {
+	pub fn new(position: Vec2, frame: u32, seed: u32) -> Self {
+		let state = 0xE9BE815E ^ position.x.to_bits() ^ position.y.to_bits().wrapping_mul(0x9e3779b9);
+		Self {
+			state: hash(state ^ hash(frame ^ hash(seed))),
+		}
+	}
+
+	fn bits(&mut self) -> u32 {
+		let mut x = self.state;
+		x ^= x << 13;
+		x ^= x >> 17;
+		x ^= x << 5;
+		self.state = x;
+		x
+	}
+
+	pub fn float(&mut self) -> f32 {
+		f32::from_bits((self.bits() >> 9) | SIGN_EXP) - 1.0
+	}
+
+	pub fn float_range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.float()
+	}
+
+	// Uniformly distributed on the unit sphere, so `normal + unit_vector()` is cosine weighted
+	pub fn unit_vector(&mut self) -> Vec3 {
+		let z = self.float_range(-1.0, 1.0);
+		let phi = 2.0 * PI * self.float();
+		let r = (1.0 - z * z).max(0.0).sqrt();
+		vec3(r * phi.cos(), r * phi.sin(), z)
+	}
+	// Uniformly distributed on the unit disk
+	pub fn in_unit_disk(&mut self) -> Vec2 {
+		let r = self.float().sqrt();
+		let phi = 2.0 * PI * self.float();
+		r * vec2(phi.cos(), phi.sin())
+	}
+}
+
+// PCG hash, used to decorrelate seeds of neighbouring pixels and frames
+pub fn hash(input: u32) -> u32 {
+	let state = input.wrapping_mul(747796405).wrapping_add(2891336453);
+	let word = ((state >> ((state >> 28) + 4)) ^ state).wrapping_mul(277803737);
+	(word >> 22) ^ word
+}
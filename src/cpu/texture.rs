@@ -0,0 +1,128 @@
+use super::rng::hash;
+use super::Scene;
+use crate::texture::{Texture, CHECKER, IMAGE, MARBLE, NOISE, SPHERICAL_CHECKER, TURBULENCE};
+use glam::{vec2, vec3, IVec3, Vec2, Vec3, Vec4};
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use std::f32::consts::PI;
+
+// The images as the GPU's texture array holds them, resized to the largest width and height and decoded from sRGB
+pub struct ImageArray {
+	width: u32,
+	height: u32,
+	layers: Vec<Vec<Vec4>>,
+}
+impl ImageArray {
+	pub fn new(images: &[RgbaImage]) -> Self {
+		let width = images.iter().map(RgbaImage::width).max().unwrap_or(1);
+		let height = images.iter().map(RgbaImage::height).max().unwrap_or(1);
+		let layers = images
+			.iter()
+			.map(|image| {
+				let resized;
+				let image = if image.dimensions() == (width, height) {
+					image
+				} else {
+					resized = imageops::resize(image, width, height, FilterType::Triangle);
+					&resized
+				};
+				image.pixels().map(|pixel| decode(pixel.0)).collect()
+			})
+			.collect();
+		Self { width, height, layers }
+	}
+
+	// Bilinear filtering with repeat addressing, as `image_sampler` in tracer.rs. Missing layers are white.
+	fn sample(&self, coordinates: Vec2, layer: u32) -> Vec3 {
+		let Some(texels) = self.layers.get(layer as usize) else {
+			return Vec3::ONE;
+		};
+		let size = vec2(self.width as f32, self.height as f32);
+		let position = coordinates * size - 0.5;
+		let base = position.floor();
+		let f = position - base;
+
+		let texel = |x: f32, y: f32| {
+			let x = (x.rem_euclid(size.x) as u32).min(self.width - 1);
+			let y = (y.rem_euclid(size.y) as u32).min(self.height - 1);
+			texels[(y * self.width + x) as usize]
+		};
+		let top = texel(base.x, base.y).lerp(texel(base.x + 1.0, base.y), f.x);
+		let bottom = texel(base.x, base.y + 1.0).lerp(texel(base.x + 1.0, base.y + 1.0), f.x);
+		top.lerp(bottom, f.y).truncate()
+	}
+}
+
+// Rgba8UnormSrgb texel to linear
+fn decode([r, g, b, a]: [u8; 4]) -> Vec4 {
+	let linear = |c: u8| {
+		let c = c as f32 / 255.0;
+		if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+	};
+	Vec4::new(linear(r), linear(g), linear(b), a as f32 / 255.0)
+}
+
+pub fn texture_value(scene: &Scene, id: u32, uv: Vec2, point: Vec3) -> Vec3 {
+	let texture = &scene.world.textures()[id as usize];
+
+	match texture.texture_type {
+		CHECKER => {
+			let cell = (point / texture.scale).floor().as_ivec3();
+			checker(texture, cell.x.wrapping_add(cell.y).wrapping_add(cell.z))
+		},
+		SPHERICAL_CHECKER => {
+			let cell = (uv * vec2(2.0 * texture.scale, texture.scale)).floor().as_ivec2();
+			checker(texture, cell.x.wrapping_add(cell.y))
+		},
+		NOISE => texture.color * 0.5 * (1.0 + perlin(texture.scale * point)),
+		TURBULENCE => texture.color * turbulence(texture.scale * point),
+		MARBLE => texture.color * 0.5 * (1.0 + (texture.scale * point.z + 10.0 * turbulence(point)).sin()),
+		IMAGE => {
+			// Image rows go from top to bottom
+			let coordinates = vec2(uv.x, 1.0 - uv.y);
+			scene.images.sample(coordinates, texture.layer) * texture.color
+		},
+		_ => texture.color,
+	}
+}
+
+fn checker(texture: &Texture, parity: i32) -> Vec3 {
+	if parity & 1 == 0 { texture.color } else { texture.odd }
+}
+
+// Random unit vector for each lattice point, see texture.wesl
+fn gradient(lattice: IVec3) -> Vec3 {
+	let h = hash(lattice.x as u32 ^ hash(lattice.y as u32 ^ hash(lattice.z as u32)));
+	let z = (h & 0xFFFF) as f32 / 32767.5 - 1.0;
+	let phi = 2.0 * PI * (h >> 16) as f32 / 65536.0;
+	let r = (1.0 - z * z).max(0.0).sqrt();
+	vec3(r * phi.cos(), r * phi.sin(), z)
+}
+
+// Gradient noise between -1 and 1
+fn perlin(point: Vec3) -> f32 {
+	let lattice = point.floor();
+	let f = point - lattice;
+	// Hermite smoothing of the interpolation weights
+	let s = f * f * (3.0 - 2.0 * f);
+
+	let mut accumulated = 0.0;
+	for i in 0..8 {
+		let corner = vec3((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32);
+		let weight = (Vec3::ONE - s) * (Vec3::ONE - corner) + s * corner;
+		accumulated += weight.x * weight.y * weight.z * gradient((lattice + corner).as_ivec3()).dot(f - corner);
+	}
+	accumulated
+}
+
+fn turbulence(point: Vec3) -> f32 {
+	let mut accumulated = 0.0;
+	let mut p = point;
+	let mut weight = 1.0;
+	for _ in 0..7 {
+		accumulated += weight * perlin(p);
+		weight *= 0.5;
+		p *= 2.0;
+	}
+	accumulated.abs()
+}
@@ -0,0 +1,485 @@
+use super::background::{background_color, environment_sampled};
+use super::light::{environment_light_pdf, light_pdf, power_heuristic, sample_light, ENVIRONMENT_DISTANCE};
+use super::material::{emitted, evaluate, scatter, scatter_pdf};
+use super::rng::Rng;
+use super::{tangents, Scene};
+use crate::bvh::BvhNode;
+use crate::disk::Disk;
+use crate::layout::{Primitive, INSTANCE, QUAD, SPHERE, TRIANGLE};
+use crate::material::MaterialReference;
+use crate::mesh::Triangle;
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::sphere::Sphere;
+use glam::{vec2, Vec2, Vec3};
+use std::f32::consts::PI;
+use std::ops::Range;
+
+// Bounds the search for the far side of a medium's boundary
+const MEDIUM_LIMIT: f32 = 10000000.0;
+// `HitRecord::primitive` of hits that aren't in the light list, i.e. planes and instances
+pub const NO_PRIMITIVE: u32 = u32::MAX;
+
+#[derive(Copy, Clone)]
+pub struct Ray {
+	pub origin: Vec3,
+	pub direction: Vec3,
+	// Moment within the shutter interval the ray samples, moving geometry is placed accordingly
+	pub time: f32,
+}
+impl Ray {
+	pub fn new(origin: Vec3, direction: Vec3, time: f32) -> Self {
+		Self {
+			origin,
+			direction,
+			time,
+		}
+	}
+	pub fn at(&self, t: f32) -> Vec3 {
+		self.origin + t * self.direction
+	}
+}
+
+#[derive(Copy, Clone)]
+pub struct HitRecord {
+	pub point: Vec3,
+	pub normal: Vec3,
+	pub t: f32,
+	pub front_face: bool,
+	pub material: MaterialReference,
+	pub uv: Vec2,
+	// Index into `SceneLayout::primitives`, set by `hit_world`
+	pub primitive: u32,
+}
+impl HitRecord {
+	pub fn new(ray: &Ray, outward_normal: Vec3, point: Vec3, t: f32, material: MaterialReference) -> Self {
+		let front_face = ray.direction.dot(outward_normal) < 0.0;
+		Self {
+			point,
+			normal: if front_face { outward_normal } else { -outward_normal },
+			t,
+			front_face,
+			material,
+			uv: Vec2::ZERO,
+			primitive: NO_PRIMITIVE,
+		}
+	}
+}
+
+fn hit_sphere(sphere: &Sphere, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let center = sphere.center + ray.time * sphere.motion;
+	let oc = center - ray.origin;
+	let a = ray.direction.dot(ray.direction);
+	let h = ray.direction.dot(oc);
+	let c = oc.dot(oc) - sphere.radius * sphere.radius;
+
+	let discriminant = h * h - a * c;
+	if discriminant < 0.0 {
+		return None;
+	}
+
+	let sqrtd = discriminant.sqrt();
+
+	let mut root = (h - sqrtd) / a;
+	if root <= t_min || t_max <= root {
+		root = (h + sqrtd) / a;
+		if root <= t_min || t_max <= root {
+			return None;
+		}
+	}
+
+	let point = ray.at(root);
+	let normal = (point - center) / sphere.radius;
+
+	let mut record = HitRecord::new(ray, normal, point, root, sphere.material);
+	record.uv = vec2(
+		((-normal.z).atan2(normal.x) + PI) / (2.0 * PI),
+		(-normal.y).clamp(-1.0, 1.0).acos() / PI,
+	);
+	Some(record)
+}
+// Möller–Trumbore
+fn hit_triangle(scene: &Scene, triangle: &Triangle, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let [v0, v1, v2] = triangle.vertices.map(|i| scene.world.vertices()[i as usize]);
+
+	let edge1 = v1.position - v0.position;
+	let edge2 = v2.position - v0.position;
+	let p = ray.direction.cross(edge2);
+	let determinant = edge1.dot(p);
+	if determinant == 0.0 {
+		return None;
+	}
+	let inverse_determinant = 1.0 / determinant;
+
+	let s = ray.origin - v0.position;
+	let b1 = s.dot(p) * inverse_determinant;
+	if !(0.0..=1.0).contains(&b1) {
+		return None;
+	}
+	let q = s.cross(edge1);
+	let b2 = ray.direction.dot(q) * inverse_determinant;
+	if b2 < 0.0 || b1 + b2 > 1.0 {
+		return None;
+	}
+	let t = edge2.dot(q) * inverse_determinant;
+	if t <= t_min || t_max <= t {
+		return None;
+	}
+	let b0 = 1.0 - b1 - b2;
+
+	let face_normal = edge1.cross(edge2).normalize();
+	let mut normal = b0 * v0.normal + b1 * v1.normal + b2 * v2.normal;
+	if normal.dot(normal) < 1e-12 {
+		normal = face_normal;
+	} else {
+		// Keep the interpolated normal on the side of the face so `front_face` stays consistent
+		normal = normal.normalize();
+		if normal.dot(face_normal) < 0.0 {
+			normal = -normal;
+		}
+	}
+
+	let mut record = HitRecord::new(ray, normal, ray.at(t), t, triangle.material);
+	record.uv = b0 * vec2(v0.u, v0.v) + b1 * vec2(v1.u, v1.v) + b2 * vec2(v2.u, v2.v);
+	Some(record)
+}
+fn hit_quad(quad: &Quad, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let n = quad.u.cross(quad.v);
+	let normal = n.normalize();
+	let denominator = normal.dot(ray.direction);
+	if denominator.abs() < 1e-8 {
+		return None;
+	}
+	let t = normal.dot(quad.q - ray.origin) / denominator;
+	if t <= t_min || t_max <= t {
+		return None;
+	}
+
+	// Coordinates of the hit point in terms of `u` and `v`
+	let point = ray.at(t);
+	let planar = point - quad.q;
+	let w = n / n.dot(n);
+	let alpha = w.dot(planar.cross(quad.v));
+	let beta = w.dot(quad.u.cross(planar));
+	if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+		return None;
+	}
+
+	let mut record = HitRecord::new(ray, normal, point, t, quad.material);
+	record.uv = vec2(alpha, beta);
+	Some(record)
+}
+fn hit_plane(plane: &Plane, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let denominator = plane.normal.dot(ray.direction);
+	if denominator.abs() < 1e-8 {
+		return None;
+	}
+	let t = plane.normal.dot(plane.point - ray.origin) / denominator;
+	if t <= t_min || t_max <= t {
+		return None;
+	}
+
+	let point = ray.at(t);
+	let [tangent, bitangent] = tangents(plane.normal);
+	let mut record = HitRecord::new(ray, plane.normal, point, t, plane.material);
+	record.uv = vec2((point - plane.point).dot(tangent), (point - plane.point).dot(bitangent));
+	Some(record)
+}
+fn hit_disk(disk: &Disk, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let denominator = disk.normal.dot(ray.direction);
+	if denominator.abs() < 1e-8 {
+		return None;
+	}
+	let t = disk.normal.dot(disk.center - ray.origin) / denominator;
+	if t <= t_min || t_max <= t {
+		return None;
+	}
+
+	let point = ray.at(t);
+	let offset = point - disk.center;
+	let distance_squared = offset.dot(offset);
+	if distance_squared > disk.radius * disk.radius {
+		return None;
+	}
+
+	// Polar coordinates, angle first
+	let [tangent, bitangent] = tangents(disk.normal);
+	let local = vec2(offset.dot(tangent), offset.dot(bitangent));
+	let mut record = HitRecord::new(ray, disk.normal, point, t, disk.material);
+	record.uv = vec2(
+		(local.y.atan2(local.x) + PI) / (2.0 * PI),
+		distance_squared.sqrt() / disk.radius,
+	);
+	Some(record)
+}
+fn hit_primitive(scene: &Scene, primitive: Primitive, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let world = scene.world;
+	let index = primitive.index as usize;
+	match primitive.kind {
+		SPHERE => hit_sphere(&world.spheres()[index], ray, t_min, t_max),
+		TRIANGLE => hit_triangle(scene, &world.triangles()[index], ray, t_min, t_max),
+		QUAD => hit_quad(&world.quads()[index], ray, t_min, t_max),
+		_ => hit_disk(&world.disks()[index], ray, t_min, t_max),
+	}
+}
+
+const NO_HIT: f32 = f32::MAX;
+// Must match bvh.wesl
+const STACK_SIZE: usize = 32;
+
+// Entry distance of the ray into the node's box, or NO_HIT if it misses within [t_min, t_max]
+fn intersect_node(node: &BvhNode, origin: Vec3, inverse_direction: Vec3, t_min: f32, t_max: f32) -> f32 {
+	let t0 = (node.min - origin) * inverse_direction;
+	let t1 = (node.max - origin) * inverse_direction;
+	let near = t0.min(t1).max_element().max(t_min);
+	let far = t0.max(t1).min_element().min(t_max);
+	if near <= far { near } else { NO_HIT }
+}
+
+// Depth-first traversal from `root` as in bvh.wesl, nearer children first. `visit` tests the primitives of a leaf
+// against the current closest distance and returns the new one.
+fn traverse(
+	scene: &Scene,
+	root: u32,
+	ray: &Ray,
+	t_min: f32,
+	t_max: f32,
+	mut visit: impl FnMut(Range<usize>, f32) -> f32,
+) {
+	let nodes = &scene.layout.nodes;
+	let inverse_direction = 1.0 / ray.direction;
+	let mut closest_so_far = t_max;
+	// The root of an empty tree has an inverted box and no children, see `Bvh::build`
+	let root_node = &nodes[root as usize];
+	if root_node.min.cmpgt(root_node.max).any()
+		|| intersect_node(root_node, ray.origin, inverse_direction, t_min, closest_so_far) == NO_HIT
+	{
+		return;
+	}
+
+	let mut stack = [0; STACK_SIZE];
+	let mut stack_size = 0;
+	let mut node_index = root;
+	loop {
+		let node = &nodes[node_index as usize];
+		if node.count > 0 {
+			let first = node.left_or_first as usize;
+			closest_so_far = visit(first..first + node.count as usize, closest_so_far);
+		} else {
+			let (mut near_index, mut far_index) = (node.left_or_first, node.left_or_first + 1);
+			let intersect = |index: u32| {
+				intersect_node(&nodes[index as usize], ray.origin, inverse_direction, t_min, closest_so_far)
+			};
+			let (mut near, mut far) = (intersect(near_index), intersect(far_index));
+			if far < near {
+				(near_index, far_index) = (far_index, near_index);
+				(near, far) = (far, near);
+			}
+
+			if near != NO_HIT {
+				if far != NO_HIT && stack_size < STACK_SIZE {
+					stack[stack_size] = far_index;
+					stack_size += 1;
+				}
+				node_index = near_index;
+				continue;
+			}
+		}
+
+		if stack_size == 0 {
+			return;
+		}
+		stack_size -= 1;
+		node_index = stack[stack_size];
+	}
+}
+
+// Traverses the object's own BVH in object space
+fn hit_object(scene: &Scene, root: u32, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+	let mut result = None;
+	traverse(scene, root, ray, t_min, t_max, |range, mut closest_so_far| {
+		for i in range {
+			if let Some(record) = hit_primitive(scene, scene.layout.primitives[i], ray, t_min, closest_so_far) {
+				closest_so_far = record.t;
+				result = Some(record);
+			}
+		}
+		closest_so_far
+	});
+	result
+}
+fn hit_instance(scene: &Scene, index: usize, ray: &Ray, t_min: f32, t_max: f32, rng: &mut Rng) -> Option<HitRecord> {
+	let instance = &scene.layout.instances[index];
+	let world_to_object = scene.world_to_object[index];
+	// The direction isn't normalized, so distances along the ray are the same in both spaces
+	let origin = ray.origin - ray.time * instance.motion;
+	let object_ray = Ray::new(
+		world_to_object.transform_point3(origin),
+		world_to_object.transform_vector3(ray.direction),
+		ray.time,
+	);
+	let result = if instance.density > 0.0 {
+		hit_medium(scene, index, &object_ray, t_min, t_max, ray.direction.length(), rng)
+	} else {
+		hit_object(scene, instance.root, &object_ray, t_min, t_max)
+	};
+	result.map(|mut record| {
+		record.point = ray.at(record.t);
+		// Normals transform with the inverse transpose
+		record.normal = world_to_object.matrix3.transpose().mul_vec3(record.normal).normalize();
+		// Instanced primitives aren't in the light list
+		record.primitive = NO_PRIMITIVE;
+		record
+	})
+}
+// Free flight sampling through the constant density medium inside the instanced object, see `hit_medium` in
+// tracing.wesl
+fn hit_medium(
+	scene: &Scene,
+	index: usize,
+	ray: &Ray,
+	t_min: f32,
+	t_max: f32,
+	ray_length: f32,
+	rng: &mut Rng,
+) -> Option<HitRecord> {
+	let instance = &scene.layout.instances[index];
+
+	// Both crossings of the boundary, even if the ray starts inside
+	let entry = hit_object(scene, instance.root, ray, -MEDIUM_LIMIT, MEDIUM_LIMIT)?;
+	let exit = hit_object(scene, instance.root, ray, entry.t + 0.0001, MEDIUM_LIMIT)?;
+
+	let start = entry.t.max(t_min);
+	let end = exit.t.min(t_max);
+	if start >= end {
+		return None;
+	}
+
+	let distance = -rng.float().ln() / instance.density;
+	if distance > (end - start) * ray_length {
+		return None;
+	}
+	Some(scatter_in_medium(ray, start + distance / ray_length, instance.material))
+}
+// Scattering point inside a medium at `t`
+fn scatter_in_medium(ray: &Ray, t: f32, material: MaterialReference) -> HitRecord {
+	// There is no surface, the normal only has to face the ray
+	HitRecord::new(ray, -ray.direction.normalize(), ray.at(t), t, material)
+}
+// Replaces the surface hit by a scattering point in the fog if the ray doesn't make it there. Rays that hit nothing
+// aren't fogged, see `SurfaceFog` in background.rs.
+fn hit_fog(scene: &Scene, ray: &Ray, result: Option<HitRecord>, rng: &mut Rng) -> Option<HitRecord> {
+	let fog_density = scene.background.fog_density;
+	let record = result?;
+	if fog_density <= 0.0 {
+		return Some(record);
+	}
+	let ray_length = ray.direction.length();
+	let distance = -rng.float().ln() / fog_density;
+	if distance >= record.t * ray_length {
+		return Some(record);
+	}
+	let material = MaterialReference::new(scene.background.fog_material);
+	Some(scatter_in_medium(ray, distance / ray_length, material))
+}
+fn hit_world(scene: &Scene, ray: &Ray, t_min: f32, t_max: f32, rng: &mut Rng) -> Option<HitRecord> {
+	let mut result = None;
+	let mut closest_so_far = t_max;
+
+	for plane in scene.world.planes() {
+		if let Some(record) = hit_plane(plane, ray, t_min, closest_so_far) {
+			closest_so_far = record.t;
+			result = Some(record);
+		}
+	}
+
+	// The top level BVH starts at node 0, its leaves hold the primitives outside of objects and the instances
+	traverse(scene, 0, ray, t_min, closest_so_far, |range, mut closest_so_far| {
+		for i in range {
+			let primitive = scene.layout.primitives[i];
+			let record = if primitive.kind == INSTANCE {
+				hit_instance(scene, primitive.index as usize, ray, t_min, closest_so_far, rng)
+			} else {
+				hit_primitive(scene, primitive, ray, t_min, closest_so_far).map(|record| HitRecord {
+					primitive: i as u32,
+					..record
+				})
+			};
+			if let Some(record) = record {
+				closest_so_far = record.t;
+				result = Some(record);
+			}
+		}
+		closest_so_far
+	});
+
+	result
+}
+pub fn occluded(scene: &Scene, point: Vec3, direction: Vec3, distance: f32, time: f32, rng: &mut Rng) -> bool {
+	// Stop short of the light itself
+	if hit_world(scene, &Ray::new(point, direction, time), 0.0001, distance * 0.999, rng).is_some() {
+		return true;
+	}
+	// Fog ends at surfaces, environment samples escape it
+	let fog_density = scene.background.fog_density;
+	if fog_density <= 0.0 || distance >= ENVIRONMENT_DISTANCE {
+		return false;
+	}
+	-rng.float().ln() / fog_density < distance * direction.length()
+}
+pub fn ray_color(scene: &Scene, ray: &Ray, rng: &mut Rng) -> Vec3 {
+	let mut radiance = Vec3::ZERO;
+	let mut throughput = Vec3::ONE;
+
+	let mut current_ray = *ray;
+	// Emission seen by camera rays and after specular bounces can't be sampled by `sample_light`, so it's not weighted
+	let mut specular = true;
+	let mut previous_point = Vec3::ZERO;
+	let mut previous_pdf = 0.0;
+
+	for _ in 0..scene.camera.max_depth {
+		let hit_result = hit_world(scene, &current_ray, 0.0001, 10000000.0, rng);
+		let Some(record) = hit_fog(scene, &current_ray, hit_result, rng) else {
+			let mut weight = 1.0;
+			if !specular && environment_sampled(scene) {
+				let environment_pdf = environment_light_pdf(scene, current_ray.direction.normalize());
+				weight = power_heuristic(previous_pdf, environment_pdf);
+			}
+			radiance += throughput * background_color(scene, &current_ray) * weight;
+			break;
+		};
+
+		let emission = emitted(scene, record.material);
+		if emission.cmpgt(Vec3::ZERO).any() {
+			let mut weight = 1.0;
+			if !specular {
+				weight = power_heuristic(previous_pdf, light_pdf(scene, previous_point, current_ray.time, &record));
+			}
+			radiance += throughput * emission * weight;
+		}
+
+		let Some(scatter_result) = scatter(scene, record.material, &current_ray, &record, rng) else {
+			break;
+		};
+
+		if !scatter_result.specular {
+			let outgoing = -current_ray.direction.normalize();
+			if let Some(light) = sample_light(scene, record.point, current_ray.time, rng)
+				&& !occluded(scene, record.point, light.direction, light.distance, current_ray.time, rng)
+			{
+				let bsdf_pdf = scatter_pdf(scene, record.material, &record, outgoing, light.direction);
+				let weight = power_heuristic(light.pdf, bsdf_pdf);
+				let bsdf = evaluate(scene, record.material, &record, outgoing, light.direction);
+				radiance += throughput * bsdf * light.radiance * weight / light.pdf;
+			}
+			previous_pdf = scatter_pdf(scene, record.material, &record, outgoing, scatter_result.ray.direction);
+		}
+		specular = scatter_result.specular;
+		previous_point = record.point;
+
+		throughput *= scatter_result.color;
+		current_ray = scatter_result.ray;
+	}
+
+	radiance
+}
@@ -0,0 +1,27 @@
+use crate::bvh::Aabb;
+use crate::material::MaterialReference;
+use glam::Vec3;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Disk {
+	pub center: Vec3,
+	pub radius: f32,
+	pub normal: Vec3,
+	pub material: MaterialReference,
+}
+impl Disk {
+	pub fn new(center: Vec3, normal: Vec3, radius: f32, material: MaterialReference) -> Self {
+		Self {
+			center,
+			radius,
+			normal: normal.normalize(),
+			material,
+		}
+	}
+	pub fn bounds(&self) -> Aabb {
+		// Extent of the rim along each axis, padded so axis-aligned disks don't get flat bounds
+		let extent = (Vec3::ONE - self.normal * self.normal).max(Vec3::ZERO).map(f32::sqrt) * self.radius + 0.0001;
+		Aabb::new(self.center - extent, self.center + extent)
+	}
+}
@@ -0,0 +1,50 @@
+// How radiance is mapped to the displayable range, applied by the blit after exposure
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub enum ToneMapper {
+	// Clips everything above 1
+	#[default]
+	Clamp,
+	Reinhard,
+	// Stephen Hill's fit of the ACES reference and output transforms
+	Aces,
+	// Troy Sobotka's AgX with the default look
+	Agx,
+}
+impl ToneMapper {
+	// Must match the constants in blit.wesl
+	pub fn id(self) -> u32 {
+		match self {
+			ToneMapper::Clamp => 0,
+			ToneMapper::Reinhard => 1,
+			ToneMapper::Aces => 2,
+			ToneMapper::Agx => 3,
+		}
+	}
+	// As in scene files and on the command line
+	pub fn name(self) -> &'static str {
+		match self {
+			ToneMapper::Clamp => "clamp",
+			ToneMapper::Reinhard => "reinhard",
+			ToneMapper::Aces => "aces",
+			ToneMapper::Agx => "agx",
+		}
+	}
+	pub fn next(self) -> Self {
+		match self {
+			ToneMapper::Clamp => ToneMapper::Reinhard,
+			ToneMapper::Reinhard => ToneMapper::Aces,
+			ToneMapper::Aces => ToneMapper::Agx,
+			ToneMapper::Agx => ToneMapper::Clamp,
+		}
+	}
+}
+
+// Turns the accumulated radiance into the image shown in the window or saved to a file
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct DisplaySettings {
+	pub tone_mapper: ToneMapper,
+	// Stops, radiance is scaled by 2^exposure before tone mapping
+	pub exposure: f32,
+	// Adds noise of one 8-bit step before quantization, hiding banding in smooth gradients
+	pub dither: bool,
+}
@@ -0,0 +1,45 @@
+use image::{ImageResult, Rgba32FImage};
+use std::f32::consts::PI;
+use std::path::Path;
+
+// Equirectangular map in linear radiance, the top row looks up. Radiance `.hdr` and OpenEXR files are supported.
+pub fn load(path: &Path) -> ImageResult<Rgba32FImage> {
+	Ok(image::open(path)?.into_rgba32f())
+}
+
+// Piecewise constant distribution over the map's texels, proportional to their luminance times the solid angle they
+// cover. Laid out as the marginal CDF over the rows (height + 1 entries) followed by the conditional CDF over the
+// columns of each row (width + 1 entries each), see `sample_environment` in background.wesl.
+pub fn distribution(map: &Rgba32FImage) -> Vec<f32> {
+	let (width, height) = map.dimensions();
+	let mut conditional = Vec::with_capacity(height as usize * (width as usize + 1));
+	let mut marginal = Vec::with_capacity(height as usize + 1);
+	marginal.push(0.0);
+
+	for y in 0..height {
+		let sin_theta = (PI * (y as f32 + 0.5) / height as f32).sin();
+		let row_start = conditional.len();
+		let mut sum = 0.0;
+		conditional.push(0.0);
+		for x in 0..width {
+			let [r, g, b, _] = map.get_pixel(x, y).0;
+			sum += (0.2126 * r + 0.7152 * g + 0.0722 * b).max(0.0) * sin_theta;
+			conditional.push(sum);
+		}
+		normalize(&mut conditional[row_start..]);
+		marginal.push(marginal[y as usize] + sum);
+	}
+	normalize(&mut marginal);
+
+	marginal.extend(conditional);
+	marginal
+}
+
+// Scales a running sum to end at 1, falling back to a uniform CDF if it's all zero
+fn normalize(cdf: &mut [f32]) {
+	let total = cdf[cdf.len() - 1];
+	let intervals = (cdf.len() - 1) as f32;
+	for (i, value) in cdf.iter_mut().enumerate() {
+		*value = if total > 0.0 { *value / total } else { i as f32 / intervals };
+	}
+}
@@ -0,0 +1,129 @@
+use std::fmt;
+use wgpu::{
+	Adapter, Backends, Device, DeviceDescriptor, Instance, InstanceDescriptor, Limits, PowerPreference, Queue,
+	RequestAdapterError, RequestAdapterOptions, RequestDeviceError, Surface,
+};
+
+#[derive(Debug, Clone, Default)]
+pub struct AdapterOptions {
+	pub backends: Option<Backends>,
+	// Case-insensitive substring of the adapter name
+	pub name: Option<String>,
+	// Only used without a `name`, wgpu picks the adapter then
+	pub power_preference: PowerPreference,
+	pub force_fallback_adapter: bool,
+}
+
+#[derive(Debug)]
+pub enum GpuError {
+	NoAdapter(RequestAdapterError),
+	NoAdapterNamed { name: String, available: Vec<String> },
+	RequestDevice(RequestDeviceError),
+	// Each entry names a limit with the required and the supported value
+	UnsupportedLimits { adapter: String, limits: Vec<(&'static str, u64, u64)> },
+}
+impl fmt::Display for GpuError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			GpuError::NoAdapter(e) => write!(f, "{e}"),
+			GpuError::NoAdapterNamed { name, available } => {
+				write!(f, "no adapter matching `{name}`, available adapters: ")?;
+				if available.is_empty() {
+					write!(f, "none")
+				} else {
+					write!(f, "{}", available.join(", "))
+				}
+			},
+			GpuError::RequestDevice(e) => write!(f, "failed to create device: {e}"),
+			GpuError::UnsupportedLimits { adapter, limits } => {
+				let limits = limits
+					.iter()
+					.map(|(name, required, supported)| format!("{name} is {supported}, {required} is needed"))
+					.collect::<Vec<_>>();
+				write!(f, "adapter {adapter} doesn't support the tracer: {}", limits.join(", "))
+			},
+		}
+	}
+}
+impl std::error::Error for GpuError {}
+
+impl AdapterOptions {
+	pub fn backends(&self) -> Backends {
+		self.backends.unwrap_or(Backends::all())
+	}
+
+	pub fn create_instance(&self) -> Instance {
+		Instance::new(&InstanceDescriptor {
+			backends: self.backends(),
+			..InstanceDescriptor::from_env_or_default()
+		})
+	}
+
+	pub async fn request_adapter(
+		&self,
+		instance: &Instance,
+		surface: Option<&Surface<'_>>,
+	) -> Result<Adapter, GpuError> {
+		let adapter = if let Some(name) = &self.name {
+			let adapters = instance.enumerate_adapters(self.backends());
+			let needle = name.to_lowercase();
+			let mut available = Vec::new();
+			let mut found = None;
+			for adapter in adapters {
+				let info = adapter.get_info();
+				available.push(format!("{} ({:?})", info.name, info.backend));
+				let compatible = surface.is_none_or(|surface| adapter.is_surface_supported(surface));
+				if found.is_none() && compatible && info.name.to_lowercase().contains(&needle) {
+					found = Some(adapter);
+				}
+			}
+			found.ok_or_else(|| GpuError::NoAdapterNamed {
+				name: name.clone(),
+				available,
+			})?
+		} else {
+			instance
+				.request_adapter(&RequestAdapterOptions {
+					power_preference: self.power_preference,
+					force_fallback_adapter: self.force_fallback_adapter,
+					compatible_surface: surface,
+				})
+				.await
+				.map_err(GpuError::NoAdapter)?
+		};
+
+		let info = adapter.get_info();
+		log::info!(
+			"Using adapter {} ({:?}, {:?}, driver: {} {})",
+			info.name,
+			info.backend,
+			info.device_type,
+			info.driver,
+			info.driver_info
+		);
+
+		Ok(adapter)
+	}
+}
+
+pub async fn request_device(adapter: &Adapter, required_limits: Limits) -> Result<(Device, Queue), GpuError> {
+	// wgpu only reports the first limit that isn't met
+	let mut limits = Vec::new();
+	required_limits.check_limits_with_fail_fn(&adapter.limits(), false, |name, required, supported| {
+		limits.push((name, required, supported));
+	});
+	if !limits.is_empty() {
+		return Err(GpuError::UnsupportedLimits {
+			adapter: adapter.get_info().name,
+			limits,
+		});
+	}
+
+	adapter
+		.request_device(&DeviceDescriptor {
+			required_limits,
+			..Default::default()
+		})
+		.await
+		.map_err(GpuError::RequestDevice)
+}
@@ -0,0 +1,55 @@
+use image::{ImageFormat, RgbaImage};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub struct Image {
+	pub width: u32,
+	pub height: u32,
+	// 8-bit sRGB encoded RGBA, row-major, top row first
+	pub pixels: Vec<u8>,
+}
+impl Image {
+	pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+		assert_eq!(pixels.len(), width as usize * height as usize * 4);
+		Self { width, height, pixels }
+	}
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		match output_format(path)? {
+			OutputFormat::Ppm => self.write_ppm(BufWriter::new(File::create(path)?)),
+			OutputFormat::Png => self.write_png(path),
+		}
+	}
+	pub fn write_png(&self, path: &Path) -> io::Result<()> {
+		let image = RgbaImage::from_raw(self.width, self.height, self.pixels.clone()).unwrap();
+		image.save_with_format(path, ImageFormat::Png).map_err(io::Error::other)
+	}
+	// Plain (P3) PPM, the format used throughout the book
+	pub fn write_ppm(&self, mut writer: impl Write) -> io::Result<()> {
+		writeln!(writer, "P3")?;
+		writeln!(writer, "{} {}", self.width, self.height)?;
+		writeln!(writer, "255")?;
+		for pixel in self.pixels.chunks_exact(4) {
+			writeln!(writer, "{} {} {}", pixel[0], pixel[1], pixel[2])?;
+		}
+		writer.flush()
+	}
+}
+
+pub enum OutputFormat {
+	Png,
+	Ppm,
+}
+
+// Chosen by the extension of `path`, so a path can be checked before rendering
+pub fn output_format(path: &Path) -> io::Result<OutputFormat> {
+	let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+	match extension.as_deref() {
+		Some("png") => Ok(OutputFormat::Png),
+		Some("ppm") => Ok(OutputFormat::Ppm),
+		_ => Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("unsupported image format for {}, expected .png or .ppm", path.display()),
+		)),
+	}
+}
@@ -0,0 +1,72 @@
+use crate::material::MaterialReference;
+use glam::{Affine3A, Vec3, Vec4};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct ObjectReference {
+	id: u32,
+}
+impl ObjectReference {
+	pub fn new(id: u32) -> Self {
+		Self { id }
+	}
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+}
+
+// Places an object in the world, the matrices are stored row by row as the last row is always (0, 0, 0, 1)
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Instance {
+	pub(crate) object_to_world: [Vec4; 3],
+	pub(crate) world_to_object: [Vec4; 3],
+	// World space translation from time 0 to time 1, applied after the transform
+	pub motion: Vec3,
+	pub object: ObjectReference,
+	// Root node of the object's BVH, filled in when the scene is uploaded
+	pub root: u32,
+	// Instances with a positive density are constant density media filling the object, which has to be closed and
+	// convex, scattering according to `material`
+	pub(crate) density: f32,
+	pub(crate) material: MaterialReference,
+	_p0: u32,
+}
+impl Instance {
+	pub fn new(object: ObjectReference, transform: Affine3A) -> Self {
+		Self {
+			object_to_world: rows(transform),
+			world_to_object: rows(transform.inverse()),
+			motion: Vec3::ZERO,
+			object,
+			root: 0,
+			density: 0.0,
+			material: MaterialReference::new(0),
+			_p0: 0,
+		}
+	}
+	pub fn with_medium(self, density: f32, material: MaterialReference) -> Self {
+		Self {
+			density,
+			material,
+			..self
+		}
+	}
+	pub fn with_motion(self, motion: Vec3) -> Self {
+		Self { motion, ..self }
+	}
+	pub fn transform(&self) -> Affine3A {
+		let [x, y, z] = self.object_to_world;
+		Affine3A::from_cols_array(&[x.x, y.x, z.x, x.y, y.y, z.y, x.z, y.z, z.z, x.w, y.w, z.w])
+	}
+}
+
+fn rows(transform: Affine3A) -> [Vec4; 3] {
+	let m = transform.matrix3;
+	let t = transform.translation;
+	[
+		Vec4::new(m.x_axis.x, m.y_axis.x, m.z_axis.x, t.x),
+		Vec4::new(m.x_axis.y, m.y_axis.y, m.z_axis.y, t.y),
+		Vec4::new(m.x_axis.z, m.y_axis.z, m.z_axis.z, t.z),
+	]
+}
@@ -0,0 +1,184 @@
+use crate::bvh::{Aabb, Bvh, BvhNode};
+use crate::instance::Instance;
+use crate::material::MaterialReference;
+use crate::world::World;
+use glam::{Affine3A, BVec3, Vec3};
+use std::ops::Range;
+
+// Entry of the list the BVH is built over, `index` points into the buffer selected by `kind`
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Primitive {
+	pub kind: u32,
+	pub index: u32,
+}
+// Must match the constants in primitive.wesl
+pub const SPHERE: u32 = 0;
+pub const TRIANGLE: u32 = 1;
+pub const QUAD: u32 = 2;
+pub const DISK: u32 = 3;
+pub const INSTANCE: u32 = 4;
+impl Primitive {
+	pub fn bounds(&self, world: &World) -> Aabb {
+		let index = self.index as usize;
+		match self.kind {
+			SPHERE => world.spheres()[index].bounds(),
+			TRIANGLE => world.triangles()[index].bounds(world.vertices()),
+			QUAD => world.quads()[index].bounds(),
+			_ => world.disks()[index].bounds(),
+		}
+	}
+	pub fn material(&self, world: &World) -> MaterialReference {
+		let index = self.index as usize;
+		match self.kind {
+			SPHERE => world.spheres()[index].material,
+			TRIANGLE => world.triangles()[index].material,
+			QUAD => world.quads()[index].material,
+			_ => world.disks()[index].material,
+		}
+	}
+}
+
+// The world flattened into the arrays both tracers traverse, the GPU one uploads them as they are
+pub struct SceneLayout {
+	// The top level BVH with its root at node 0, followed by the BVH of every object
+	pub nodes: Vec<BvhNode>,
+	// In the order the leaves expect, the top level's come first
+	pub primitives: Vec<Primitive>,
+	// `World::instances` with `root` filled in
+	pub instances: Vec<Instance>,
+	// Indices into `primitives` of the emissive primitives `sample_light` picks from
+	pub lights: Vec<u32>,
+}
+impl SceneLayout {
+	pub fn new(world: &World) -> Self {
+		let primitive_range = |kind, range: Range<usize>| {
+			range.map(move |index| Primitive {
+				kind,
+				index: index as u32,
+			})
+		};
+		let primitives_in = |spheres, triangles, quads, disks| {
+			primitive_range(SPHERE, spheres)
+				.chain(primitive_range(TRIANGLE, triangles))
+				.chain(primitive_range(QUAD, quads))
+				.chain(primitive_range(DISK, disks))
+		};
+
+		// Every object gets its own BVH, instances refer to its root node
+		let objects = world
+			.objects()
+			.iter()
+			.map(|object| {
+				let primitives = primitives_in(
+					object.spheres.clone(),
+					object.triangles.clone(),
+					object.quads.clone(),
+					object.disks.clone(),
+				)
+				.collect::<Vec<_>>();
+				let bvh = Bvh::build(&primitives.iter().map(|p| p.bounds(world)).collect::<Vec<_>>());
+				(bvh, primitives)
+			})
+			.collect::<Vec<_>>();
+
+		// The top level BVH holds the instances and all primitives outside of objects. Membership is indexed by kind,
+		// SPHERE through DISK, then by primitive.
+		let mut in_object = [
+			vec![false; world.spheres().len()],
+			vec![false; world.triangles().len()],
+			vec![false; world.quads().len()],
+			vec![false; world.disks().len()],
+		];
+		for object in world.objects() {
+			let ranges = [&object.spheres, &object.triangles, &object.quads, &object.disks];
+			for (members, range) in in_object.iter_mut().zip(ranges) {
+				members[range.clone()].fill(true);
+			}
+		}
+		let mut top_level = primitives_in(
+			0..world.spheres().len(),
+			0..world.triangles().len(),
+			0..world.quads().len(),
+			0..world.disks().len(),
+		)
+		.filter(|primitive| !in_object[primitive.kind as usize][primitive.index as usize])
+		.collect::<Vec<_>>();
+		let mut top_level_bounds = top_level.iter().map(|p| p.bounds(world)).collect::<Vec<_>>();
+		for (index, instance) in world.instances().iter().enumerate() {
+			let (bvh, _) = &objects[instance.object.id() as usize];
+			if let Some(bounds) = transform_bounds(&bvh.nodes[0], instance.transform()) {
+				top_level.push(Primitive {
+					kind: INSTANCE,
+					index: index as u32,
+				});
+				// Covers the whole path from time 0 to 1
+				let end = Aabb::new(bounds.min + instance.motion, bounds.max + instance.motion);
+				top_level_bounds.push(bounds.union(end));
+			}
+		}
+
+		// The top level comes first so its root is node 0
+		let mut nodes = Vec::new();
+		let mut primitives = Vec::new();
+		append_bvh(&mut nodes, &mut primitives, Bvh::build(&top_level_bounds), &top_level);
+		let top_level_count = primitives.len();
+		let roots = objects
+			.into_iter()
+			.map(|(bvh, object_primitives)| append_bvh(&mut nodes, &mut primitives, bvh, &object_primitives))
+			.collect::<Vec<_>>();
+		let instances = world
+			.instances()
+			.iter()
+			.map(|instance| {
+				let mut instance = *instance;
+				instance.root = roots[instance.object.id() as usize];
+				instance
+			})
+			.collect::<Vec<_>>();
+
+		// Planes are unbounded and can't be sampled, so they're never lights, neither are instanced primitives
+		let lights = primitives[..top_level_count]
+			.iter()
+			.enumerate()
+			.filter(|(_, primitive)| {
+				primitive.kind != INSTANCE && world.materials()[primitive.material(world).id() as usize].is_emissive()
+			})
+			.map(|(i, _)| i as u32)
+			.collect::<Vec<_>>();
+
+		Self {
+			nodes,
+			primitives,
+			instances,
+			lights,
+		}
+	}
+}
+
+// Appends a BVH built over `unordered` to the shared node and primitive lists, returns its root node
+fn append_bvh(nodes: &mut Vec<BvhNode>, primitives: &mut Vec<Primitive>, bvh: Bvh, unordered: &[Primitive]) -> u32 {
+	let node_offset = nodes.len() as u32;
+	let primitive_offset = primitives.len() as u32;
+	nodes.extend(bvh.nodes.iter().map(|node| BvhNode {
+		left_or_first: node.left_or_first + if node.count > 0 { primitive_offset } else { node_offset },
+		..*node
+	}));
+	primitives.extend(bvh.indices.iter().map(|&i| unordered[i as usize]));
+	node_offset
+}
+
+// World space bounds of a node placed by `transform`, `None` if the node is empty
+fn transform_bounds(node: &BvhNode, transform: Affine3A) -> Option<Aabb> {
+	if node.min.cmpgt(node.max).any() {
+		return None;
+	}
+	Some((0..8).fold(Aabb::EMPTY, |bounds, corner| {
+		let corner = Vec3::select(
+			BVec3::new(corner & 1 != 0, corner & 2 != 0, corner & 4 != 0),
+			node.max,
+			node.min,
+		);
+		bounds.grow(transform.transform_point3(corner))
+	}))
+}
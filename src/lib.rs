@@ -0,0 +1,38 @@
+// GPU path tracer based on Ray Tracing in One Weekend. Scenes are built with `World` and rendered offscreen with
+// `Renderer` (or `CpuRenderer` without a GPU) or interactively with `App`.
+
+pub mod app;
+pub mod background;
+mod bvh;
+pub mod camera;
+pub mod cpu;
+pub mod disk;
+pub mod display;
+pub mod environment;
+pub mod gpu;
+pub mod image;
+pub mod instance;
+mod layout;
+pub mod material;
+pub mod mesh;
+pub mod obj;
+pub mod plane;
+pub mod quad;
+pub mod renderer;
+pub mod scene_file;
+pub mod scenes;
+pub mod screenshot;
+pub mod sphere;
+mod state;
+pub mod texture;
+mod tracer;
+pub mod world;
+
+pub use crate::camera::CameraParameters;
+pub use crate::cpu::CpuRenderer;
+pub use crate::image::Image;
+pub use crate::material::Material;
+pub use crate::renderer::{RenderError, RenderSettings, Renderer};
+pub use crate::sphere::Sphere;
+pub use crate::state::WindowRenderer;
+pub use crate::world::World;
@@ -1,113 +1,147 @@
-mod app;
-mod camera;
-mod material;
-mod sphere;
-mod state;
-mod world;
-
-use crate::app::App;
-use crate::camera::CameraParameters;
-use crate::material::Material;
-use crate::sphere::Sphere;
-use crate::world::World;
-use glam::vec3;
-use std::f32::consts::FRAC_PI_2;
+mod cli;
+
+use crate::cli::{Cli, Command};
+use rtiow_wgpu::app::App;
+use rtiow_wgpu::display::DisplaySettings;
+use rtiow_wgpu::image;
+use rtiow_wgpu::scene_file::{self, SceneFile};
+use rtiow_wgpu::scenes;
+use rtiow_wgpu::screenshot::SceneSource;
+use rtiow_wgpu::{CpuRenderer, RenderSettings, Renderer};
+use clap::Parser;
+use std::path::Path;
+use std::process::ExitCode;
+use winit::dpi::PhysicalSize;
 use winit::event_loop::{ControlFlow, EventLoop};
 
-fn main() {
-	env_logger::init();
-	let event_loop = EventLoop::new().unwrap();
-	event_loop.set_control_flow(ControlFlow::Poll);
-	let mut app = App::new();
-	event_loop.run_app(&mut app).unwrap();
-}
-
-mod rng {
-	use glam::{vec3, Vec3};
-	use std::sync::atomic::{AtomicU32, Ordering};
-
-	static RNG_STATE: AtomicU32 = AtomicU32::new(0xE9BE815E);
-
-	pub fn f32() -> f32 {
-		const SIGN_EXP: u32 = 0x3F800000;
-
-		let mut x = RNG_STATE.load(Ordering::Relaxed);
-		x ^= x << 13;
-		x ^= x >> 17;
-		x ^= x << 5;
-		RNG_STATE.store(x, Ordering::Relaxed);
-		f32::from_bits((x >> 9) | SIGN_EXP) - 1.0
-	}
-
-	pub fn f32_range(min: f32, max: f32) -> f32 {
-		min + (max - min) * f32()
-	}
-	pub fn vector() -> Vec3 {
-		vec3(f32(), f32(), f32())
-	}
-}
-
-fn setup() -> (World, CameraParameters) {
-	let world = {
-		let mut world = World::new();
-
-		let ground_material = world.add_material(Material::lambertian(vec3(0.5, 0.5, 0.5)));
-		world.add_sphere(Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0, ground_material));
-
-		for a in -11..11 {
-			for b in -11..11 {
-				let a = a as f32;
-				let b = b as f32;
-
-				let choose_mat = rng::f32();
-				let center = vec3(a + 0.9 * rng::f32(), 0.2, b + 0.9 * rng::f32());
-
-				if (center - vec3(4.0, 0.2, 0.0)).length() > 0.9 {
-					let sphere_material;
-
-					if choose_mat < 0.8 {
-						// diffuse
-						let albedo = rng::vector() * rng::vector();
-						sphere_material = world.add_material(Material::lambertian(albedo));
-						world.add_sphere(Sphere::new(center, 0.2, sphere_material));
-					} else if choose_mat < 0.95 {
-						// metal
-						let albedo = rng::vector() * rng::vector();
-						let fuzz = rng::f32_range(0.0, 0.5);
-						sphere_material = world.add_material(Material::metal(albedo, fuzz));
-						world.add_sphere(Sphere::new(center, 0.2, sphere_material));
-					} else {
-						// glass
-						sphere_material = world.add_material(Material::dielectric(1.5));
-						world.add_sphere(Sphere::new(center, 0.2, sphere_material));
-					}
+fn main() -> ExitCode {
+	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("rtiow_wgpu=info")).init();
+	let cli = Cli::parse();
+	let adapter_options = cli.adapter.options();
+
+	let SceneFile {
+		world,
+		mut camera_parameters,
+		width,
+		height,
+		passes,
+		mut display,
+	} = match load_scene(&cli.scene.scene, cli.scene.seed.unwrap_or(0)) {
+		Ok(scene) => scene,
+		Err(e) => {
+			eprintln!("{e}");
+			return ExitCode::FAILURE;
+		},
+	};
+	cli.scene.apply(&mut camera_parameters);
+	cli.scene.display.apply(&mut display);
+	let width = cli.scene.width.or(width);
+	let height = cli.scene.height.or(height);
+
+	match cli.command.unwrap_or(Command::Interactive) {
+		Command::Interactive => {
+			let size = match (width, height) {
+				(Some(width), Some(height)) => Some(PhysicalSize::new(width, height)),
+				(None, None) => None,
+				_ => {
+					eprintln!("--width and --height must be given together");
+					return ExitCode::FAILURE;
+				},
+			};
+			// Screenshots can be re-rendered from another directory
+			let is_builtin = scenes::SCENES.iter().any(|(name, _)| *name == cli.scene.scene);
+			let scene = match std::path::absolute(&cli.scene.scene) {
+				Ok(path) if !is_builtin => path.to_string_lossy().into_owned(),
+				_ => cli.scene.scene.clone(),
+			};
+			let source = SceneSource {
+				scene,
+				seed: cli.scene.seed,
+			};
+
+			let event_loop = EventLoop::new().unwrap();
+			event_loop.set_control_flow(ControlFlow::Poll);
+			let mut app = App::new(world, camera_parameters, display, source, size, adapter_options);
+			event_loop.run_app(&mut app).unwrap();
+			if let Some(e) = app.error() {
+				eprintln!("{e}");
+				return ExitCode::FAILURE;
+			}
+		},
+		Command::Render {
+			output,
+			passes: cli_passes,
+			cpu,
+		} => {
+			// Fails before the render rather than after it
+			if let Err(e) = image::output_format(&output) {
+				eprintln!("{e}");
+				return ExitCode::FAILURE;
+			}
+			let passes = cli_passes.or(passes).unwrap_or(10);
+			let spp = camera_parameters.samples_per_pixel;
+			let Some(samples) = spp.checked_mul(passes) else {
+				eprintln!("{passes} passes of {spp} samples per pixel exceed {} samples", u32::MAX);
+				return ExitCode::FAILURE;
+			};
+			let settings = RenderSettings {
+				width: width.unwrap_or(1280),
+				height: height.unwrap_or(720),
+				samples,
+				display,
+			};
+			let renderer = if cpu {
+				None
+			} else {
+				match pollster::block_on(Renderer::new(&adapter_options)) {
+					Ok(renderer) => Some(renderer),
+					Err(e) => {
+						log::warn!("{e}, rendering on the CPU");
+						None
+					},
 				}
+			};
+			let image = match renderer {
+				Some(renderer) => renderer.render_to_image(&world, &camera_parameters, settings),
+				None => CpuRenderer::new().render_to_image(&world, &camera_parameters, settings),
+			};
+			let image = match image {
+				Ok(image) => image,
+				Err(e) => {
+					eprintln!("{e}");
+					return ExitCode::FAILURE;
+				},
+			};
+			if let Err(e) = image.save(&output) {
+				eprintln!("failed to write {}: {e}", output.display());
+				return ExitCode::FAILURE;
 			}
-		}
-
-		let material1 = world.add_material(Material::dielectric(1.5));
-		world.add_sphere(Sphere::new(vec3(0.0, 1.0, 0.0), 1.0, material1));
-
-		let material2 = world.add_material(Material::lambertian(vec3(0.4, 0.2, 0.1)));
-		world.add_sphere(Sphere::new(vec3(-4.0, 1.0, 0.0), 1.0, material2));
-
-		let material3 = world.add_material(Material::metal(vec3(0.7, 0.6, 0.5), 0.0));
-		world.add_sphere(Sphere::new(vec3(4.0, 1.0, 0.0), 1.0, material3));
+		},
+	}
 
-		world
-	};
+	ExitCode::SUCCESS
+}
 
-	let pitch = 0.0;
-	let yaw = FRAC_PI_2;
+fn load_scene(name: &str, seed: u32) -> Result<SceneFile, String> {
+	if let Some((world, camera_parameters)) = scenes::by_name(name, seed) {
+		return Ok(SceneFile {
+			world,
+			camera_parameters,
+			width: None,
+			height: None,
+			passes: None,
+			display: DisplaySettings::default(),
+		});
+	}
 
-	let camera_parameters = CameraParameters {
-		samples_per_pixel: 10,
-		max_depth: 10,
-		fov: 75.0,
-		location: vec3(13.0, 2.0, 3.0),
-		pitch,
-		yaw,
-	};
+	let path = Path::new(name);
+	if path.extension().is_some_and(|e| e == "toml") || path.exists() {
+		return scene_file::load(path).map_err(|e| e.to_string());
+	}
 
-	(world, camera_parameters)
+	let names = scenes::SCENES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+	Err(format!(
+		"unknown scene `{name}`, expected a .toml file or one of: {}",
+		names.join(", ")
+	))
 }
@@ -1,3 +1,4 @@
+use crate::texture::{TextureReference, NO_TEXTURE};
 use glam::{vec4, Vec3, Vec4};
 
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
@@ -9,43 +10,99 @@ impl MaterialReference {
 	pub fn new(id: u32) -> Self {
 		Self { id }
 	}
+	pub fn id(&self) -> u32 {
+		self.id
+	}
 }
 
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct Material {
-	data: Vec4,
-	material_type: u32,
-	_p0: u32,
+	pub(crate) data: Vec4,
+	// Roughness, specular, clearcoat and clearcoat roughness of microfacet materials
+	pub(crate) parameters: Vec4,
+	pub(crate) material_type: u32,
+	// Albedo of lambertian, metal and isotropic materials and base colour of microfacet materials, `NO_TEXTURE` uses
+	// the constant in `data`
+	pub(crate) texture: u32,
 	_p1: u32,
 	_p2: u32,
 }
 impl Material {
-	pub fn lambertian(albedo: Vec3) -> Self {
+	fn new(material_type: u32, data: Vec4) -> Self {
 		Self {
-			data: albedo.extend(0.0),
-			material_type: 0,
-			_p0: 0,
+			data,
+			parameters: Vec4::ZERO,
+			material_type,
+			texture: NO_TEXTURE,
 			_p1: 0,
 			_p2: 0,
 		}
 	}
+	pub fn lambertian(albedo: Vec3) -> Self {
+		Self::new(0, albedo.extend(0.0))
+	}
 	pub fn metal(albedo: Vec3, fuzz: f32) -> Self {
+		Self::new(1, albedo.extend(fuzz))
+	}
+	pub fn lambertian_textured(albedo: TextureReference) -> Self {
 		Self {
-			data: albedo.extend(fuzz),
-			material_type: 1,
-			_p0: 0,
-			_p1: 0,
-			_p2: 0,
+			texture: albedo.id(),
+			..Self::lambertian(Vec3::ZERO)
+		}
+	}
+	pub fn metal_textured(albedo: TextureReference, fuzz: f32) -> Self {
+		Self {
+			texture: albedo.id(),
+			..Self::metal(Vec3::ZERO, fuzz)
 		}
 	}
 	pub fn dielectric(refraction_index: f32) -> Self {
+		Self::new(2, vec4(refraction_index, 0.0, 0.0, 0.0))
+	}
+	pub fn emissive(color: Vec3, intensity: f32) -> Self {
+		Self::new(3, color.extend(intensity))
+	}
+	// Scatters uniformly in all directions, the phase function of fog and smoke
+	pub fn isotropic(albedo: Vec3) -> Self {
+		Self::new(4, albedo.extend(0.0))
+	}
+	pub fn isotropic_textured(albedo: TextureReference) -> Self {
 		Self {
-			data: vec4(refraction_index, 0.0, 0.0, 0.0),
-			material_type: 2,
-			_p0: 0,
-			_p1: 0,
-			_p2: 0,
+			texture: albedo.id(),
+			..Self::isotropic(Vec3::ZERO)
+		}
+	}
+	// GGX metallic/roughness material, a diffuse base under a specular layer tinted by `base_color` as it gets
+	// metallic. `roughness` is perceptual, the GGX alpha is its square. Specular is 0.5 and there is no clearcoat,
+	// see `with_specular` and `with_clearcoat`.
+	pub fn microfacet(base_color: Vec3, metallic: f32, roughness: f32) -> Self {
+		Self {
+			parameters: vec4(roughness, 0.5, 0.0, 0.0),
+			..Self::new(5, base_color.extend(metallic))
+		}
+	}
+	pub fn microfacet_textured(base_color: TextureReference, metallic: f32, roughness: f32) -> Self {
+		Self {
+			texture: base_color.id(),
+			..Self::microfacet(Vec3::ZERO, metallic, roughness)
 		}
 	}
+	// Reflectance of dielectric microfacet materials at normal incidence, 0.5 is 4% like most dielectrics
+	pub fn with_specular(self, specular: f32) -> Self {
+		Self {
+			parameters: vec4(self.parameters.x, specular, self.parameters.z, self.parameters.w),
+			..self
+		}
+	}
+	// Adds a colourless specular coat with its own roughness on top of a microfacet material
+	pub fn with_clearcoat(self, clearcoat: f32, roughness: f32) -> Self {
+		Self {
+			parameters: vec4(self.parameters.x, self.parameters.y, clearcoat, roughness),
+			..self
+		}
+	}
+	pub fn is_emissive(&self) -> bool {
+		self.material_type == 3
+	}
 }
@@ -0,0 +1,41 @@
+use crate::bvh::Aabb;
+use crate::material::MaterialReference;
+use glam::{Vec2, Vec3};
+
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Vertex {
+	pub position: Vec3,
+	pub u: f32,
+	// A zero normal makes the shader fall back to the face normal
+	pub normal: Vec3,
+	pub v: f32,
+}
+impl Vertex {
+	pub fn new(position: Vec3, normal: Vec3, uv: Vec2) -> Self {
+		Self {
+			position,
+			u: uv.x,
+			normal,
+			v: uv.y,
+		}
+	}
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Triangle {
+	// Indices into `World::vertices`
+	pub vertices: [u32; 3],
+	pub material: MaterialReference,
+}
+impl Triangle {
+	pub fn new(vertices: [u32; 3], material: MaterialReference) -> Self {
+		Self { vertices, material }
+	}
+	pub fn bounds(&self, vertices: &[Vertex]) -> Aabb {
+		self.vertices
+			.iter()
+			.fold(Aabb::EMPTY, |bounds, &i| bounds.grow(vertices[i as usize].position))
+	}
+}
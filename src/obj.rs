@@ -0,0 +1,134 @@
+use crate::material::{Material, MaterialReference};
+use crate::mesh::Vertex;
+use crate::texture::{Texture, TextureReference};
+use crate::world::World;
+use glam::{Mat3, Mat4, Vec2, Vec3};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug)]
+pub struct ObjError {
+	pub path: PathBuf,
+	pub error: tobj::LoadError,
+}
+impl fmt::Display for ObjError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.path.display(), self.error)
+	}
+}
+impl std::error::Error for ObjError {}
+
+pub fn load(
+	world: &mut World,
+	path: &Path,
+	transform: Mat4,
+	default_material: MaterialReference,
+) -> Result<(), ObjError> {
+	let (models, materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(|error| ObjError {
+		path: path.to_owned(),
+		error,
+	})?;
+
+	// A missing or broken .mtl isn't fatal, the meshes fall back to `default_material`
+	let materials = materials.unwrap_or_else(|e| {
+		log::warn!("{}: failed to load materials: {e}", path.display());
+		Vec::new()
+	});
+	let directory = path.parent().unwrap_or(Path::new(""));
+	let materials = materials
+		.iter()
+		.map(|material| {
+			// Like the .mtl itself, a texture that fails to load leaves the material untextured
+			let texture = material.diffuse_texture.as_ref().and_then(|texture| {
+				let texture_path = directory.join(texture);
+				let layer = world
+					.load_image(&texture_path)
+					.inspect_err(|e| log::warn!("{}: failed to load texture: {e}", texture_path.display()))
+					.ok()?;
+				Some(world.add_texture(Texture::image(layer)))
+			});
+			world.add_material(convert_material(material, texture))
+		})
+		.collect::<Vec<_>>();
+
+	let normal_transform = Mat3::from_mat4(transform).inverse().transpose();
+	for model in &models {
+		let mesh = &model.mesh;
+		let vertices = mesh
+			.positions
+			.chunks_exact(3)
+			.enumerate()
+			.map(|(i, position)| {
+				let normal = mesh.normals.get(3 * i..3 * i + 3).map_or(Vec3::ZERO, |n| {
+					(normal_transform * Vec3::from_slice(n)).normalize_or_zero()
+				});
+				let uv = mesh
+					.texcoords
+					.get(2 * i..2 * i + 2)
+					.map_or(Vec2::ZERO, Vec2::from_slice);
+				Vertex::new(transform.transform_point3(Vec3::from_slice(position)), normal, uv)
+			})
+			.collect::<Vec<_>>();
+		let indices = mesh
+			.indices
+			.chunks_exact(3)
+			.map(|face| [face[0], face[1], face[2]])
+			.collect::<Vec<_>>();
+		let material = mesh
+			.material_id
+			.and_then(|id| materials.get(id).copied())
+			.unwrap_or(default_material);
+		world.add_mesh(&vertices, &indices, material);
+	}
+
+	Ok(())
+}
+
+// Maps the MTL illumination models onto our materials, anything unsupported becomes lambertian. `texture` is the
+// loaded `map_Kd` and replaces the diffuse colour.
+fn convert_material(material: &tobj::Material, texture: Option<TextureReference>) -> Material {
+	let diffuse = material.diffuse.map_or(Vec3::splat(0.8), Vec3::from);
+
+	let emission = material.unknown_param.get("Ke").and_then(|ke| {
+		let values = ke
+			.split_whitespace()
+			.map(str::parse)
+			.collect::<Result<Vec<f32>, _>>()
+			.ok()?;
+		match values[..] {
+			[x, y, z] => Some(Vec3::new(x, y, z)),
+			[v] => Some(Vec3::splat(v)),
+			_ => None,
+		}
+	});
+	if let Some(emission) = emission.filter(|e| e.max_element() > 0.0) {
+		let intensity = emission.max_element();
+		return Material::emissive(emission / intensity, intensity);
+	}
+
+	// The PBR extension of the format
+	let parameter = |key: &str| material.unknown_param.get(key).and_then(|value| value.trim().parse::<f32>().ok());
+	let (metallic, roughness) = (parameter("Pm"), parameter("Pr"));
+	if metallic.is_some() || roughness.is_some() {
+		let (metallic, roughness) = (metallic.unwrap_or(0.0), roughness.unwrap_or(0.5));
+		let material = match texture {
+			Some(texture) => Material::microfacet_textured(texture, metallic, roughness),
+			None => Material::microfacet(diffuse, metallic, roughness),
+		};
+		return material.with_clearcoat(parameter("Pc").unwrap_or(0.0), parameter("Pcr").unwrap_or(0.03));
+	}
+
+	match material.illumination_model {
+		// Refraction
+		Some(4 | 6 | 7 | 9) => Material::dielectric(material.optical_density.unwrap_or(1.5)),
+		// Reflection, `Ns` ranges from 0 to 1000 with higher values being sharper
+		Some(3 | 5 | 8) => {
+			let fuzz = (2.0 / (material.shininess.unwrap_or(0.0).max(0.0) + 2.0)).sqrt();
+			Material::metal(material.specular.map_or(diffuse, Vec3::from), fuzz)
+		},
+		_ => match texture {
+			Some(texture) => Material::lambertian_textured(texture),
+			None => Material::lambertian(diffuse),
+		},
+	}
+}
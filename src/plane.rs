@@ -0,0 +1,22 @@
+use crate::material::MaterialReference;
+use glam::Vec3;
+
+// Unbounded, so planes are tested for every ray instead of being part of the BVH
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Plane {
+	pub point: Vec3,
+	pub material: MaterialReference,
+	pub normal: Vec3,
+	_p0: u32,
+}
+impl Plane {
+	pub fn new(point: Vec3, normal: Vec3, material: MaterialReference) -> Self {
+		Self {
+			point,
+			material,
+			normal: normal.normalize(),
+			_p0: 0,
+		}
+	}
+}
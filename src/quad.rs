@@ -0,0 +1,57 @@
+use crate::bvh::Aabb;
+use crate::material::MaterialReference;
+use glam::Vec3;
+
+// Keeps axis-aligned quads from having flat bounds
+const BOUNDS_PADDING: f32 = 0.0001;
+
+// Parallelogram spanned by `u` and `v` from the corner `q`
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Quad {
+	pub q: Vec3,
+	pub material: MaterialReference,
+	pub u: Vec3,
+	_p0: u32,
+	pub v: Vec3,
+	_p1: u32,
+}
+impl Quad {
+	pub fn new(q: Vec3, u: Vec3, v: Vec3, material: MaterialReference) -> Self {
+		Self {
+			q,
+			material,
+			u,
+			_p0: 0,
+			v,
+			_p1: 0,
+		}
+	}
+	pub fn bounds(&self) -> Aabb {
+		let padding = Vec3::splat(BOUNDS_PADDING);
+		let bounds = Aabb::EMPTY
+			.grow(self.q)
+			.grow(self.q + self.u)
+			.grow(self.q + self.v)
+			.grow(self.q + self.u + self.v);
+		Aabb::new(bounds.min - padding, bounds.max + padding)
+	}
+}
+
+// The six sides of the axis-aligned box with opposite corners `a` and `b`
+pub fn box_sides(a: Vec3, b: Vec3, material: MaterialReference) -> [Quad; 6] {
+	let min = a.min(b);
+	let max = a.max(b);
+	let dx = Vec3::new(max.x - min.x, 0.0, 0.0);
+	let dy = Vec3::new(0.0, max.y - min.y, 0.0);
+	let dz = Vec3::new(0.0, 0.0, max.z - min.z);
+
+	[
+		Quad::new(Vec3::new(min.x, min.y, max.z), dx, dy, material),
+		Quad::new(Vec3::new(max.x, min.y, max.z), -dz, dy, material),
+		Quad::new(Vec3::new(max.x, min.y, min.z), -dx, dy, material),
+		Quad::new(Vec3::new(min.x, min.y, min.z), dz, dy, material),
+		Quad::new(Vec3::new(min.x, max.y, max.z), dx, -dz, material),
+		Quad::new(Vec3::new(min.x, min.y, min.z), dx, dz, material),
+	]
+}
@@ -0,0 +1,152 @@
+use crate::camera::CameraParameters;
+use crate::display::DisplaySettings;
+use crate::gpu::{self, AdapterOptions, GpuError};
+use crate::image::Image;
+pub use crate::tracer::CaptureError;
+use crate::tracer::Tracer;
+use crate::world::World;
+use std::fmt;
+use wgpu::{Device, Queue, TextureFormat};
+
+#[derive(Debug, Copy, Clone)]
+pub struct RenderSettings {
+	pub width: u32,
+	pub height: u32,
+	// Total samples per pixel, traced in frames of `CameraParameters::samples_per_pixel`. Rounded up to a whole number
+	// of frames, so 10 samples at 4 samples per pixel trace 12.
+	pub samples: u32,
+	pub display: DisplaySettings,
+}
+impl RenderSettings {
+	pub fn validate(&self, camera_parameters: &CameraParameters) -> Result<(), RenderError> {
+		if self.width == 0 || self.height == 0 {
+			return Err(RenderError::EmptyImage {
+				width: self.width,
+				height: self.height,
+			});
+		}
+		// The shaders average over the samples of a frame
+		if self.samples == 0 || camera_parameters.samples_per_pixel == 0 {
+			return Err(RenderError::NoSamples);
+		}
+		Ok(())
+	}
+}
+
+// Settings that can't be rendered, reported before any work is done
+#[derive(Debug)]
+pub enum RenderError {
+	EmptyImage { width: u32, height: u32 },
+	NoSamples,
+	Capture(CaptureError),
+}
+impl fmt::Display for RenderError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			RenderError::EmptyImage { width, height } => {
+				write!(f, "image size must be at least 1x1, got {width}x{height}")
+			},
+			RenderError::NoSamples => write!(f, "samples per pixel must be at least 1"),
+			RenderError::Capture(e) => e.fmt(f),
+		}
+	}
+}
+impl std::error::Error for RenderError {}
+
+const TARGET_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+// Offscreen renderer, the device is reused across images. See `WindowRenderer` for rendering into a window.
+pub struct Renderer {
+	device: Device,
+	queue: Queue,
+}
+
+impl Renderer {
+	pub async fn new(adapter_options: &AdapterOptions) -> Result<Self, GpuError> {
+		let instance = adapter_options.create_instance();
+		let adapter = adapter_options.request_adapter(&instance, None).await?;
+		let (device, queue) = gpu::request_device(&adapter, Tracer::required_limits()).await?;
+		Ok(Self { device, queue })
+	}
+
+	// Blocks until the image is done
+	pub fn render_to_image(
+		&self,
+		world: &World,
+		camera_parameters: &CameraParameters,
+		settings: RenderSettings,
+	) -> Result<Image, RenderError> {
+		settings.validate(camera_parameters)?;
+		let RenderSettings {
+			width,
+			height,
+			samples,
+			display,
+		} = settings;
+		let (device, queue) = (&self.device, &self.queue);
+
+		let mut tracer = Tracer::new(device, queue, TARGET_FORMAT, world, *camera_parameters, width, height);
+		tracer.set_display(queue, display);
+
+		let frames = samples.div_ceil(camera_parameters.samples_per_pixel);
+		for _ in 0..frames {
+			tracer.camera.update_buffer(queue);
+			// One submission per tile keeps each batch of GPU work short enough to avoid driver timeouts
+			for tile in 0..tracer.tile_count() {
+				let mut encoder = device.create_command_encoder(&Default::default());
+				tracer.trace_tiles(&mut encoder, tile..tile + 1);
+				queue.submit([encoder.finish()]);
+			}
+			tracer.finish_frame();
+		}
+
+		tracer.capture(device, queue).map_err(RenderError::Capture)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::scenes;
+
+	fn rejection(settings: RenderSettings, camera_parameters: &CameraParameters) -> String {
+		match settings.validate(camera_parameters) {
+			Ok(()) => panic!("accepted invalid settings"),
+			Err(e) => e.to_string(),
+		}
+	}
+
+	fn settings() -> RenderSettings {
+		RenderSettings {
+			width: 96,
+			height: 64,
+			samples: 32,
+			display: DisplaySettings::default(),
+		}
+	}
+
+	#[test]
+	fn empty_images_are_rejected() {
+		let (_, camera_parameters) = scenes::by_name("sphere-light", 0).unwrap();
+		assert!(settings().validate(&camera_parameters).is_ok());
+		assert_eq!(
+			rejection(RenderSettings { width: 0, ..settings() }, &camera_parameters),
+			"image size must be at least 1x1, got 0x64"
+		);
+		assert_eq!(
+			rejection(RenderSettings { height: 0, ..settings() }, &camera_parameters),
+			"image size must be at least 1x1, got 96x0"
+		);
+	}
+
+	#[test]
+	fn missing_samples_are_rejected() {
+		let (_, mut camera_parameters) = scenes::by_name("sphere-light", 0).unwrap();
+		assert_eq!(
+			rejection(RenderSettings { samples: 0, ..settings() }, &camera_parameters),
+			"samples per pixel must be at least 1"
+		);
+		camera_parameters.samples_per_pixel = 0;
+		assert_eq!(rejection(settings(), &camera_parameters), "samples per pixel must be at least 1");
+	}
+}
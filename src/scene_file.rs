@@ -0,0 +1,880 @@
+use crate::background::{Background, SurfaceFog};
+use crate::camera::{check_defocus_angle, check_focus_dist, check_fov, CameraParameters};
+use crate::disk::Disk;
+use crate::display::{DisplaySettings, ToneMapper};
+use crate::material::{Material, MaterialReference};
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::sphere::Sphere;
+use crate::texture::{Texture, TextureReference};
+use crate::world::World;
+use glam::{Affine3A, Mat4, Quat, Vec3};
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use toml::Spanned;
+
+pub struct SceneFile {
+	pub world: World,
+	pub camera_parameters: CameraParameters,
+	pub width: Option<u32>,
+	pub height: Option<u32>,
+	pub passes: Option<u32>,
+	pub display: DisplaySettings,
+}
+
+#[derive(Debug)]
+pub struct SceneError {
+	pub path: PathBuf,
+	// 1-based line and column
+	pub location: Option<(usize, usize)>,
+	pub message: String,
+}
+impl fmt::Display for SceneError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self.location {
+			Some((line, column)) => write!(f, "{}:{line}:{column}: {}", self.path.display(), self.message),
+			None => write!(f, "{}: {}", self.path.display(), self.message),
+		}
+	}
+}
+impl std::error::Error for SceneError {}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDescription {
+	#[serde(default)]
+	camera: CameraDescription,
+	#[serde(default)]
+	render: RenderDescription,
+	background: Option<Spanned<BackgroundDescription>>,
+	#[serde(default)]
+	textures: BTreeMap<String, Spanned<TextureDescription>>,
+	#[serde(default)]
+	materials: BTreeMap<String, Spanned<MaterialDescription>>,
+	#[serde(default)]
+	spheres: Vec<SphereDescription>,
+	#[serde(default)]
+	quads: Vec<Spanned<QuadDescription>>,
+	#[serde(default)]
+	planes: Vec<PlaneDescription>,
+	#[serde(default)]
+	disks: Vec<DiskDescription>,
+	#[serde(default)]
+	boxes: Vec<Spanned<BoxDescription>>,
+	#[serde(default)]
+	meshes: Vec<MeshDescription>,
+	#[serde(default)]
+	volumes: Vec<Spanned<VolumeDescription>>,
+	surface_fog: Option<SurfaceFogDescription>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct CameraDescription {
+	position: Spanned<[f32; 3]>,
+	look_at: Option<Spanned<[f32; 3]>>,
+	// Radians, only used without `look_at`
+	pitch: Spanned<f32>,
+	yaw: Spanned<f32>,
+	fov: Spanned<f32>,
+	// Degrees, 0 disables depth of field
+	defocus_angle: Spanned<f32>,
+	// Defaults to the distance to `look_at`
+	focus_dist: Option<Spanned<f32>>,
+	// Open and close time, moving spheres go from `center` at 0 to `end_center` at 1
+	shutter: Spanned<[f32; 2]>,
+}
+impl Default for CameraDescription {
+	fn default() -> Self {
+		Self {
+			position: Spanned::new(0..0, [0.0; 3]),
+			look_at: None,
+			pitch: Spanned::new(0..0, 0.0),
+			yaw: Spanned::new(0..0, 0.0),
+			fov: Spanned::new(0..0, 90.0),
+			defocus_angle: Spanned::new(0..0, 0.0),
+			focus_dist: None,
+			shutter: Spanned::new(0..0, [0.0, 1.0]),
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct RenderDescription {
+	samples_per_pixel: Spanned<u32>,
+	max_depth: u32,
+	width: Option<Spanned<u32>>,
+	height: Option<Spanned<u32>>,
+	passes: Option<Spanned<u32>>,
+	// clamp, reinhard, aces or agx
+	tone_mapper: Option<Spanned<String>>,
+	// Stops
+	exposure: Spanned<f32>,
+	dither: bool,
+	// Seed of the per pixel random numbers
+	seed: u32,
+}
+impl Default for RenderDescription {
+	fn default() -> Self {
+		Self {
+			samples_per_pixel: Spanned::new(0..0, 10),
+			max_depth: 10,
+			width: None,
+			height: None,
+			passes: None,
+			tone_mapper: None,
+			exposure: Spanned::new(0..0, 0.0),
+			dither: false,
+			seed: 0,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BackgroundDescription {
+	#[serde(rename = "type")]
+	background_type: Spanned<String>,
+	color: Option<Spanned<[f32; 3]>>,
+	// Environment map relative to the scene file
+	path: Option<Spanned<PathBuf>>,
+	// Degrees around the y axis
+	#[serde(default = "default_rotation")]
+	rotation: Spanned<f32>,
+	intensity: Option<Spanned<f32>>,
+}
+fn default_rotation() -> Spanned<f32> {
+	Spanned::new(0..0, 0.0)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TextureDescription {
+	#[serde(rename = "type")]
+	texture_type: Spanned<String>,
+	// Solid colour or tint of the noise and image textures
+	color: Option<Spanned<[f32; 3]>>,
+	even: Option<Spanned<[f32; 3]>>,
+	odd: Option<Spanned<[f32; 3]>>,
+	scale: Option<Spanned<f32>>,
+	// Relative to the scene file
+	path: Option<Spanned<PathBuf>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MaterialDescription {
+	#[serde(rename = "type")]
+	material_type: Spanned<String>,
+	albedo: Option<Spanned<[f32; 3]>>,
+	// Name of a texture used instead of `albedo`
+	texture: Option<Spanned<String>>,
+	fuzz: Option<Spanned<f32>>,
+	refraction_index: Option<Spanned<f32>>,
+	color: Option<Spanned<[f32; 3]>>,
+	intensity: Option<Spanned<f32>>,
+	// Microfacet parameters between 0 and 1, `albedo` or `texture` is the base colour
+	metallic: Option<Spanned<f32>>,
+	roughness: Option<Spanned<f32>>,
+	specular: Option<Spanned<f32>>,
+	clearcoat: Option<Spanned<f32>>,
+	clearcoat_roughness: Option<Spanned<f32>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SphereDescription {
+	center: Spanned<[f32; 3]>,
+	// Makes the sphere move, see `CameraDescription::shutter`
+	end_center: Option<Spanned<[f32; 3]>>,
+	radius: Spanned<f32>,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct QuadDescription {
+	q: Spanned<[f32; 3]>,
+	u: Spanned<[f32; 3]>,
+	v: Spanned<[f32; 3]>,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PlaneDescription {
+	point: Spanned<[f32; 3]>,
+	normal: Spanned<[f32; 3]>,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiskDescription {
+	center: Spanned<[f32; 3]>,
+	normal: Spanned<[f32; 3]>,
+	radius: Spanned<f32>,
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxDescription {
+	min: Spanned<[f32; 3]>,
+	max: Spanned<[f32; 3]>,
+	material: Spanned<String>,
+	// Displacement from time 0 to 1, see `CameraDescription::shutter`
+	motion: Option<Spanned<[f32; 3]>>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MeshDescription {
+	// Relative to the scene file
+	path: Spanned<PathBuf>,
+	// Used for meshes without a material in the .mtl file
+	material: Option<Spanned<String>>,
+	#[serde(default = "default_position")]
+	position: Spanned<[f32; 3]>,
+	#[serde(default = "default_scale")]
+	scale: Spanned<f32>,
+	// Displacement from time 0 to 1, see `CameraDescription::shutter`. Moving meshes aren't sampled as lights.
+	motion: Option<Spanned<[f32; 3]>>,
+}
+fn default_position() -> Spanned<[f32; 3]> {
+	Spanned::new(0..0, [0.0; 3])
+}
+fn default_scale() -> Spanned<f32> {
+	Spanned::new(0..0, 1.0)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct VolumeDescription {
+	// `sphere` with `center` and `radius` or `box` with `min` and `max`
+	shape: Spanned<String>,
+	center: Option<Spanned<[f32; 3]>>,
+	radius: Option<Spanned<f32>>,
+	min: Option<Spanned<[f32; 3]>>,
+	max: Option<Spanned<[f32; 3]>>,
+	density: Spanned<f32>,
+	// Usually isotropic
+	material: Spanned<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SurfaceFogDescription {
+	density: Spanned<f32>,
+	material: Spanned<String>,
+}
+
+pub fn load(path: &Path) -> Result<SceneFile, SceneError> {
+	let source = std::fs::read_to_string(path).map_err(|e| SceneError {
+		path: path.to_owned(),
+		location: None,
+		message: e.to_string(),
+	})?;
+	parse(&source, path)
+}
+
+pub fn parse(source: &str, path: &Path) -> Result<SceneFile, SceneError> {
+	let error = |span: Option<Range<usize>>, message: String| SceneError {
+		path: path.to_owned(),
+		location: span.map(|span| line_column(source, span.start)),
+		message,
+	};
+
+	let description: SceneDescription = toml::from_str(source).map_err(|e| error(e.span(), e.message().to_owned()))?;
+
+	// NaN and infinity would spread through the BVH and the shaders, blanking the image
+	let vector = |value: &Spanned<[f32; 3]>, key: &str| {
+		let vector = Vec3::from(*value.get_ref());
+		if !vector.is_finite() {
+			return Err(error(Some(value.span()), format!("{key} must be finite")));
+		}
+		Ok(vector)
+	};
+	let scalar = |value: &Spanned<f32>, key: &str| {
+		if !value.get_ref().is_finite() {
+			return Err(error(Some(value.span()), format!("{key} must be finite")));
+		}
+		Ok(*value.get_ref())
+	};
+
+	let mut world = World::new();
+
+	let directory = path.parent().unwrap_or(Path::new(""));
+	if let Some(background) = &description.background {
+		let span = background.span();
+		let background = background.get_ref();
+		let background = match background.background_type.get_ref().as_str() {
+			"sky" => Background::Sky,
+			"solid" => {
+				let Some(color) = &background.color else {
+					return Err(error(Some(span), "solid background is missing `color`".to_owned()));
+				};
+				Background::Solid(vector(color, "color")?)
+			},
+			"environment" => {
+				let Some(map_path) = &background.path else {
+					return Err(error(Some(span), "environment background is missing `path`".to_owned()));
+				};
+				world
+					.load_environment_map(&directory.join(map_path.get_ref()))
+					.map_err(|e| error(Some(map_path.span()), e.to_string()))?;
+				let intensity = match &background.intensity {
+					Some(intensity) if !non_negative(*intensity.get_ref()) => {
+						return Err(error(
+							Some(intensity.span()),
+							"environment intensity must not be negative".to_owned(),
+						));
+					},
+					Some(intensity) => *intensity.get_ref(),
+					None => 1.0,
+				};
+				Background::Environment {
+					rotation: scalar(&background.rotation, "rotation")?.to_radians(),
+					intensity,
+				}
+			},
+			other => {
+				return Err(error(
+					Some(background.background_type.span()),
+					format!("unknown background type `{other}`, expected sky, solid or environment"),
+				));
+			},
+		};
+		world.set_background(background);
+	}
+
+	let mut textures = HashMap::new();
+	for (name, description) in &description.textures {
+		let span = description.span();
+		let description = description.get_ref();
+		let color = |color: &Option<Spanned<[f32; 3]>>, key: &str| match color {
+			Some(color) => vector(color, key),
+			None => Err(error(Some(span.clone()), format!("texture `{name}` is missing `{key}`"))),
+		};
+		let scale = match &description.scale {
+			Some(scale) if !positive(*scale.get_ref()) => {
+				return Err(error(
+					Some(scale.span()),
+					format!("scale of texture `{name}` must be positive"),
+				));
+			},
+			Some(scale) => *scale.get_ref(),
+			None => 1.0,
+		};
+		let tint = match &description.color {
+			Some(color) => vector(color, "color")?,
+			None => Vec3::ONE,
+		};
+
+		let texture = match description.texture_type.get_ref().as_str() {
+			"solid" => world.add_texture(Texture::solid(color(&description.color, "color")?)),
+			"checker" => world.add_texture(Texture::checker(
+				scale,
+				color(&description.even, "even")?,
+				color(&description.odd, "odd")?,
+			)),
+			"spherical-checker" => world.add_texture(Texture::spherical_checker(
+				scale,
+				color(&description.even, "even")?,
+				color(&description.odd, "odd")?,
+			)),
+			"noise" => world.add_texture(Texture::noise(scale).with_color(tint)),
+			"turbulence" => world.add_texture(Texture::turbulence(scale).with_color(tint)),
+			"marble" => world.add_texture(Texture::marble(scale).with_color(tint)),
+			"image" => {
+				let Some(image_path) = &description.path else {
+					return Err(error(Some(span), format!("texture `{name}` is missing `path`")));
+				};
+				let layer = world
+					.load_image(&directory.join(image_path.get_ref()))
+					.map_err(|e| error(Some(image_path.span()), e.to_string()))?;
+				world.add_texture(Texture::image(layer).with_color(tint))
+			},
+			other => {
+				return Err(error(
+					Some(description.texture_type.span()),
+					format!(
+						"unknown texture type `{other}`, expected solid, checker, spherical-checker, noise, turbulence, \
+						 marble or image"
+					),
+				));
+			},
+		};
+		textures.insert(name.as_str(), texture);
+	}
+
+	let mut materials = HashMap::new();
+	for (name, description) in &description.materials {
+		let span = description.span();
+		let description = description.get_ref();
+		let texture = |texture: &Spanned<String>| -> Result<TextureReference, SceneError> {
+			textures
+				.get(texture.get_ref().as_str())
+				.copied()
+				.ok_or_else(|| error(Some(texture.span()), format!("unknown texture `{}`", texture.get_ref())))
+		};
+		let albedo = || match &description.albedo {
+			Some(albedo) => vector(albedo, "albedo"),
+			None => Err(error(Some(span.clone()), format!("material `{name}` is missing `albedo`"))),
+		};
+		if description.albedo.is_some() && description.texture.is_some() {
+			return Err(error(
+				Some(span),
+				format!("material `{name}` has both `albedo` and `texture`"),
+			));
+		}
+
+		let material = match description.material_type.get_ref().as_str() {
+			"lambertian" => match &description.texture {
+				Some(name) => Material::lambertian_textured(texture(name)?),
+				None => Material::lambertian(albedo()?),
+			},
+			"metal" => {
+				let fuzz = match &description.fuzz {
+					Some(fuzz) if !(0.0..=1.0).contains(fuzz.get_ref()) => {
+						return Err(error(
+							Some(fuzz.span()),
+							format!("fuzz of material `{name}` must be between 0 and 1"),
+						));
+					},
+					Some(fuzz) => *fuzz.get_ref(),
+					None => 0.0,
+				};
+				match &description.texture {
+					Some(name) => Material::metal_textured(texture(name)?, fuzz),
+					None => Material::metal(albedo()?, fuzz),
+				}
+			},
+			"dielectric" => {
+				let Some(refraction_index) = &description.refraction_index else {
+					return Err(error(
+						Some(span),
+						format!("material `{name}` is missing `refraction_index`"),
+					));
+				};
+				if !positive(*refraction_index.get_ref()) {
+					return Err(error(
+						Some(refraction_index.span()),
+						format!("refraction index of material `{name}` must be positive"),
+					));
+				}
+				Material::dielectric(*refraction_index.get_ref())
+			},
+			"emissive" => {
+				let Some(color) = &description.color else {
+					return Err(error(Some(span), format!("material `{name}` is missing `color`")));
+				};
+				let color = vector(color, "color")?;
+				let intensity = match &description.intensity {
+					Some(intensity) if !non_negative(*intensity.get_ref()) => {
+						return Err(error(
+							Some(intensity.span()),
+							format!("intensity of material `{name}` must not be negative"),
+						));
+					},
+					Some(intensity) => *intensity.get_ref(),
+					None => 1.0,
+				};
+				Material::emissive(color, intensity)
+			},
+			"isotropic" => match &description.texture {
+				Some(name) => Material::isotropic_textured(texture(name)?),
+				None => Material::isotropic(albedo()?),
+			},
+			"microfacet" => {
+				let unit = |value: &Option<Spanned<f32>>, key: &str, default: f32| match value {
+					Some(value) if !(0.0..=1.0).contains(value.get_ref()) => Err(error(
+						Some(value.span()),
+						format!("{key} of material `{name}` must be between 0 and 1"),
+					)),
+					Some(value) => Ok(*value.get_ref()),
+					None => Ok(default),
+				};
+				let metallic = unit(&description.metallic, "metallic", 0.0)?;
+				let roughness = unit(&description.roughness, "roughness", 0.5)?;
+				let material = match &description.texture {
+					Some(name) => Material::microfacet_textured(texture(name)?, metallic, roughness),
+					None => Material::microfacet(albedo()?, metallic, roughness),
+				};
+				material
+					.with_specular(unit(&description.specular, "specular", 0.5)?)
+					.with_clearcoat(
+						unit(&description.clearcoat, "clearcoat", 0.0)?,
+						unit(&description.clearcoat_roughness, "clearcoat_roughness", 0.03)?,
+					)
+			},
+			other => {
+				return Err(error(
+					Some(description.material_type.span()),
+					format!(
+						"unknown material type `{other}`, expected lambertian, metal, dielectric, emissive, isotropic or \
+						 microfacet"
+					),
+				));
+			},
+		};
+		materials.insert(name.as_str(), world.add_material(material));
+	}
+
+	let material = |name: &Spanned<String>| -> Result<MaterialReference, SceneError> {
+		materials
+			.get(name.get_ref().as_str())
+			.copied()
+			.ok_or_else(|| error(Some(name.span()), format!("unknown material `{}`", name.get_ref())))
+	};
+
+	for sphere in &description.spheres {
+		let radius = *sphere.radius.get_ref();
+		if !positive(radius) {
+			return Err(error(Some(sphere.radius.span()), "radius must be positive".to_owned()));
+		}
+		let center = vector(&sphere.center, "center")?;
+		let end_center = match &sphere.end_center {
+			Some(end_center) => vector(end_center, "end_center")?,
+			None => center,
+		};
+		world.add_sphere(Sphere::moving(center, end_center, radius, material(&sphere.material)?));
+	}
+
+	let normal = |normal: &Spanned<[f32; 3]>| {
+		let direction = vector(normal, "normal")?;
+		if direction.length_squared() == 0.0 {
+			return Err(error(Some(normal.span()), "normal must be non-zero".to_owned()));
+		}
+		Ok(direction)
+	};
+	// Boxes without volume have coinciding sides
+	let corners = |min: &Spanned<[f32; 3]>, max: &Spanned<[f32; 3]>, span: Range<usize>| {
+		let (min, max) = (vector(min, "min")?, vector(max, "max")?);
+		if min.cmpeq(max).any() {
+			return Err(error(Some(span), "box must have a non-zero size along every axis".to_owned()));
+		}
+		Ok((min, max))
+	};
+
+	for quad in &description.quads {
+		let span = quad.span();
+		let quad = quad.get_ref();
+		let (q, u, v) = (vector(&quad.q, "q")?, vector(&quad.u, "u")?, vector(&quad.v, "v")?);
+		// The normal is the cross product of the sides
+		if u.cross(v).length_squared() == 0.0 {
+			return Err(error(Some(span), "quad sides u and v must be non-zero and not parallel".to_owned()));
+		}
+		world.add_quad(Quad::new(q, u, v, material(&quad.material)?));
+	}
+	for plane in &description.planes {
+		world.add_plane(Plane::new(
+			vector(&plane.point, "point")?,
+			normal(&plane.normal)?,
+			material(&plane.material)?,
+		));
+	}
+	for disk in &description.disks {
+		let radius = *disk.radius.get_ref();
+		if !positive(radius) {
+			return Err(error(Some(disk.radius.span()), "radius must be positive".to_owned()));
+		}
+		world.add_disk(Disk::new(
+			vector(&disk.center, "center")?,
+			normal(&disk.normal)?,
+			radius,
+			material(&disk.material)?,
+		));
+	}
+	for cuboid in &description.boxes {
+		let span = cuboid.span();
+		let cuboid = cuboid.get_ref();
+		let ((min, max), material) = (corners(&cuboid.min, &cuboid.max, span)?, material(&cuboid.material)?);
+		match &cuboid.motion {
+			// Only instances move as a whole
+			Some(motion) => {
+				let motion = vector(motion, "motion")?;
+				let object = world.add_object(|world| world.add_box(min, max, material));
+				world.add_moving_instance(object, Affine3A::IDENTITY, motion);
+			},
+			None => world.add_box(min, max, material),
+		}
+	}
+
+	let mut fallback_material = None;
+	for mesh in &description.meshes {
+		let default_material = match &mesh.material {
+			Some(name) => material(name)?,
+			None => {
+				*fallback_material.get_or_insert_with(|| world.add_material(Material::lambertian(Vec3::splat(0.8))))
+			},
+		};
+		let mesh_path = directory.join(mesh.path.get_ref());
+		// The normal matrix is the inverse transpose, a zero scale would make it NaN
+		let scale = *mesh.scale.get_ref();
+		if !positive(scale) {
+			return Err(error(Some(mesh.scale.span()), "mesh scale must be positive".to_owned()));
+		}
+		let position = vector(&mesh.position, "position")?;
+		let transform = Mat4::from_scale_rotation_translation(Vec3::splat(scale), Quat::IDENTITY, position);
+		let result = match &mesh.motion {
+			Some(motion) => {
+				let motion = vector(motion, "motion")?;
+				let mut result = Ok(());
+				let object = world.add_object(|world| result = world.load_obj(&mesh_path, transform, default_material));
+				world.add_moving_instance(object, Affine3A::IDENTITY, motion);
+				result
+			},
+			None => world.load_obj(&mesh_path, transform, default_material),
+		};
+		result.map_err(|e| error(Some(mesh.path.span()), e.to_string()))?;
+	}
+
+	let density = |density: &Spanned<f32>| {
+		if !positive(*density.get_ref()) {
+			return Err(error(Some(density.span()), "density must be positive".to_owned()));
+		}
+		Ok(*density.get_ref())
+	};
+	for volume in &description.volumes {
+		let span = volume.span();
+		let volume = volume.get_ref();
+		let missing = |key: &str| {
+			error(
+				Some(span.clone()),
+				format!("{} volume is missing `{key}`", volume.shape.get_ref()),
+			)
+		};
+		let (density, material) = (density(&volume.density)?, material(&volume.material)?);
+		let boundary = match volume.shape.get_ref().as_str() {
+			"sphere" => {
+				let center = vector(volume.center.as_ref().ok_or_else(|| missing("center"))?, "center")?;
+				let radius = volume.radius.as_ref().ok_or_else(|| missing("radius"))?;
+				if !positive(*radius.get_ref()) {
+					return Err(error(Some(radius.span()), "radius must be positive".to_owned()));
+				}
+				let radius = *radius.get_ref();
+				world.add_object(|world| world.add_sphere(Sphere::new(center, radius, material)))
+			},
+			"box" => {
+				let min = volume.min.as_ref().ok_or_else(|| missing("min"))?;
+				let max = volume.max.as_ref().ok_or_else(|| missing("max"))?;
+				let (min, max) = corners(min, max, span.clone())?;
+				world.add_object(|world| world.add_box(min, max, material))
+			},
+			other => {
+				return Err(error(
+					Some(volume.shape.span()),
+					format!("unknown volume shape `{other}`, expected sphere or box"),
+				));
+			},
+		};
+		world.add_medium(boundary, Affine3A::IDENTITY, density, material);
+	}
+	if let Some(fog) = &description.surface_fog {
+		world.set_surface_fog(SurfaceFog {
+			density: density(&fog.density)?,
+			material: material(&fog.material)?,
+		});
+	}
+
+	let camera = &description.camera;
+	let position = vector(&camera.position, "position")?;
+	let look_at = camera.look_at.as_ref().map(|look_at| vector(look_at, "look_at")).transpose()?;
+	let fov = check_fov(*camera.fov.get_ref()).map_err(|e| error(Some(camera.fov.span()), e))?;
+	let defocus_angle =
+		check_defocus_angle(*camera.defocus_angle.get_ref()).map_err(|e| error(Some(camera.defocus_angle.span()), e))?;
+	let focus_dist = match &camera.focus_dist {
+		Some(focus_dist) => check_focus_dist(*focus_dist.get_ref()).map_err(|e| error(Some(focus_dist.span()), e))?,
+		None => look_at.map(|look_at| look_at.distance(position)).filter(|&distance| distance > 0.0).unwrap_or(10.0),
+	};
+	// Motion is only defined between times 0 and 1, the BVH bounds don't cover anything beyond
+	let [shutter_open, shutter_close] = *camera.shutter.get_ref();
+	if !(0.0 <= shutter_open && shutter_open <= shutter_close && shutter_close <= 1.0) {
+		return Err(error(
+			Some(camera.shutter.span()),
+			"shutter must be an interval [open, close] within [0, 1]".to_owned(),
+		));
+	}
+	let render = &description.render;
+	if *render.samples_per_pixel.get_ref() == 0 {
+		return Err(error(
+			Some(render.samples_per_pixel.span()),
+			"samples_per_pixel must be at least 1".to_owned(),
+		));
+	}
+	for (value, key) in [(&render.width, "width"), (&render.height, "height"), (&render.passes, "passes")] {
+		if let Some(value) = value
+			&& *value.get_ref() == 0
+		{
+			return Err(error(Some(value.span()), format!("{key} must be at least 1")));
+		}
+	}
+	let tone_mapper = match &render.tone_mapper {
+		Some(name) => match name.get_ref().as_str() {
+			"clamp" => ToneMapper::Clamp,
+			"reinhard" => ToneMapper::Reinhard,
+			"aces" => ToneMapper::Aces,
+			"agx" => ToneMapper::Agx,
+			other => {
+				return Err(error(
+					Some(name.span()),
+					format!("unknown tone mapper `{other}`, expected clamp, reinhard, aces or agx"),
+				));
+			},
+		},
+		None => ToneMapper::default(),
+	};
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: *render.samples_per_pixel.get_ref(),
+		max_depth: render.max_depth,
+		fov,
+		location: position,
+		pitch: scalar(&camera.pitch, "pitch")?,
+		yaw: scalar(&camera.yaw, "yaw")?,
+		defocus_angle,
+		focus_dist,
+		shutter_open,
+		shutter_close,
+		seed: render.seed,
+	};
+	if let Some(look_at) = look_at {
+		camera_parameters.look_at(look_at);
+	}
+
+	Ok(SceneFile {
+		world,
+		camera_parameters,
+		width: render.width.as_ref().map(|width| *width.get_ref()),
+		height: render.height.as_ref().map(|height| *height.get_ref()),
+		passes: render.passes.as_ref().map(|passes| *passes.get_ref()),
+		display: DisplaySettings {
+			tone_mapper,
+			exposure: scalar(&render.exposure, "exposure")?,
+			dither: render.dither,
+		},
+	})
+}
+
+// Comparisons are false for NaN, so these are written to fail for it. Infinite values are rejected as well.
+fn positive(value: f32) -> bool {
+	value > 0.0 && value.is_finite()
+}
+fn non_negative(value: f32) -> bool {
+	value >= 0.0 && value.is_finite()
+}
+
+fn line_column(source: &str, offset: usize) -> (usize, usize) {
+	let before = &source[..offset.min(source.len())];
+	let line = before.matches('\n').count() + 1;
+	let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+	(line, column)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const MATERIALS: &str = "[materials]\nground = { type = \"lambertian\", albedo = [0.5, 0.5, 0.5] }\n";
+
+	fn parse_error(source: &str) -> String {
+		match parse(source, Path::new("scene.toml")) {
+			Ok(_) => panic!("parsed an invalid scene"),
+			Err(e) => e.to_string(),
+		}
+	}
+
+	fn sphere(radius: &str, material: &str) -> String {
+		format!("{MATERIALS}\n[[spheres]]\ncenter = [0.0, 0.0, 0.0]\nradius = {radius}\nmaterial = \"{material}\"\n")
+	}
+
+	#[test]
+	fn unknown_material() {
+		assert_eq!(parse_error(&sphere("1.0", "grass")), "scene.toml:7:12: unknown material `grass`");
+	}
+
+	#[test]
+	fn invalid_radius() {
+		for radius in ["-1.0", "0.0", "nan", "inf"] {
+			assert_eq!(parse_error(&sphere(radius, "ground")), "scene.toml:6:10: radius must be positive", "{radius}");
+		}
+	}
+
+	#[test]
+	fn invalid_mesh_scale() {
+		for scale in ["0.0", "-2.0", "nan"] {
+			assert_eq!(
+				parse_error(&format!("[[meshes]]\npath = \"missing.obj\"\nscale = {scale}\n")),
+				"scene.toml:3:9: mesh scale must be positive",
+				"{scale}"
+			);
+		}
+	}
+
+	#[test]
+	fn non_finite_vectors() {
+		assert_eq!(
+			parse_error(&format!(
+				"{MATERIALS}\n[[spheres]]\ncenter = [0.0, nan, 0.0]\nradius = 1.0\nmaterial = \"ground\"\n"
+			)),
+			"scene.toml:5:10: center must be finite"
+		);
+		assert_eq!(parse_error("[camera]\nposition = [inf, 0.0, 0.0]\n"), "scene.toml:2:12: position must be finite");
+		assert_eq!(
+			parse_error("[background]\ntype = \"solid\"\ncolor = [1.0, 1.0, -inf]\n"),
+			"scene.toml:3:9: color must be finite"
+		);
+	}
+
+	#[test]
+	fn degenerate_shapes() {
+		let quad = |u: &str, v: &str| {
+			format!("{MATERIALS}\n[[quads]]\nq = [0.0, 0.0, 0.0]\nu = {u}\nv = {v}\nmaterial = \"ground\"\n")
+		};
+		let error = "scene.toml:4:1: quad sides u and v must be non-zero and not parallel";
+		assert_eq!(parse_error(&quad("[1.0, 0.0, 0.0]", "[2.0, 0.0, 0.0]")), error);
+		assert_eq!(parse_error(&quad("[0.0, 0.0, 0.0]", "[0.0, 1.0, 0.0]")), error);
+
+		assert_eq!(
+			parse_error(&format!(
+				"{MATERIALS}\n[[boxes]]\nmin = [0.0, 0.0, 0.0]\nmax = [1.0, 0.0, 1.0]\nmaterial = \"ground\"\n"
+			)),
+			"scene.toml:4:1: box must have a non-zero size along every axis"
+		);
+	}
+
+	#[test]
+	fn zero_size() {
+		assert_eq!(parse_error("[render]\nwidth = 0\n"), "scene.toml:2:9: width must be at least 1");
+		assert_eq!(parse_error("[render]\nheight = 0\n"), "scene.toml:2:10: height must be at least 1");
+		assert_eq!(parse_error("[render]\npasses = 0\n"), "scene.toml:2:10: passes must be at least 1");
+	}
+
+	#[test]
+	fn wrong_type() {
+		assert_eq!(
+			parse_error("[render]\nexposure = \"bright\"\n"),
+			"scene.toml:2:12: invalid type: string \"bright\", expected f32"
+		);
+	}
+
+	#[test]
+	fn line_and_column() {
+		let source = "a\nbc\n\u{e9}d";
+		assert_eq!(line_column(source, 0), (1, 1));
+		assert_eq!(line_column(source, 3), (2, 2));
+		// Columns count characters, not bytes
+		assert_eq!(line_column(source, source.len() - 1), (3, 2));
+	}
+
+	#[test]
+	fn valid_scene() {
+		let scene = parse(&sphere("1.0", "ground"), Path::new("scene.toml")).unwrap();
+		assert_eq!(scene.world.spheres().len(), 1);
+	}
+}
@@ -0,0 +1,423 @@
+use crate::background::Background;
+use crate::camera::CameraParameters;
+use crate::disk::Disk;
+use crate::material::{Material, MaterialReference};
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::sphere::Sphere;
+use crate::texture::Texture;
+use crate::world::World;
+use glam::{vec3, Affine3A, Quat, Vec3};
+use std::f32::consts::FRAC_PI_2;
+
+pub const DEFAULT_SCENE: &str = "random-spheres";
+// Takes the seed of the randomly generated scenes, the others ignore it
+pub type SceneFn = fn(u32) -> (World, CameraParameters);
+
+pub const SCENES: &[(&str, SceneFn)] = &[
+	("random-spheres", random_spheres),
+	("bouncing-spheres", bouncing_spheres),
+	("materials", materials),
+	("sphere-light", sphere_light),
+	("cornell-box", cornell_box),
+	("cornell-smoke", cornell_smoke),
+	("checkered-spheres", checkered_spheres),
+	("perlin-spheres", perlin_spheres),
+	("microfacet-spheres", microfacet_spheres),
+];
+
+// The seed also goes into the camera, so the same name and seed always trace the same image
+pub fn by_name(name: &str, seed: u32) -> Option<(World, CameraParameters)> {
+	let (_, scene) = SCENES.iter().find(|(n, _)| *n == name)?;
+	let (world, mut camera_parameters) = scene(seed);
+	camera_parameters.seed = seed;
+	Some((world, camera_parameters))
+}
+
+// xorshift, only used to place the spheres
+struct Rng(u32);
+impl Rng {
+	const DEFAULT_SEED: u32 = 0xE9BE815E;
+
+	fn new(seed: u32) -> Self {
+		// xorshift never leaves the all-zero state
+		Self(if seed == 0 { Self::DEFAULT_SEED } else { seed })
+	}
+
+	fn f32(&mut self) -> f32 {
+		const SIGN_EXP: u32 = 0x3F800000;
+
+		let mut x = self.0;
+		x ^= x << 13;
+		x ^= x >> 17;
+		x ^= x << 5;
+		self.0 = x;
+		f32::from_bits((x >> 9) | SIGN_EXP) - 1.0
+	}
+
+	fn f32_range(&mut self, min: f32, max: f32) -> f32 {
+		min + (max - min) * self.f32()
+	}
+	fn vector(&mut self) -> Vec3 {
+		vec3(self.f32(), self.f32(), self.f32())
+	}
+}
+
+fn random_spheres(seed: u32) -> (World, CameraParameters) {
+	spheres(seed, false)
+}
+
+// The first scene of "The Next Week", the diffuse spheres bounce up while the shutter is open
+fn bouncing_spheres(seed: u32) -> (World, CameraParameters) {
+	spheres(seed, true)
+}
+
+fn spheres(seed: u32, bouncing: bool) -> (World, CameraParameters) {
+	let mut rng = Rng::new(seed);
+	let world = {
+		let mut world = World::new();
+
+		let ground_material = world.add_material(Material::lambertian(vec3(0.5, 0.5, 0.5)));
+		world.add_sphere(Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0, ground_material));
+
+		for a in -11..11 {
+			for b in -11..11 {
+				let a = a as f32;
+				let b = b as f32;
+
+				let choose_mat = rng.f32();
+				let center = vec3(a + 0.9 * rng.f32(), 0.2, b + 0.9 * rng.f32());
+
+				if (center - vec3(4.0, 0.2, 0.0)).length() > 0.9 {
+					let sphere_material;
+
+					if choose_mat < 0.8 {
+						// diffuse
+						let albedo = rng.vector() * rng.vector();
+						sphere_material = world.add_material(Material::lambertian(albedo));
+						if bouncing {
+							let end = center + vec3(0.0, rng.f32_range(0.0, 0.5), 0.0);
+							world.add_sphere(Sphere::moving(center, end, 0.2, sphere_material));
+						} else {
+							world.add_sphere(Sphere::new(center, 0.2, sphere_material));
+						}
+					} else if choose_mat < 0.95 {
+						// metal
+						let albedo = rng.vector() * rng.vector();
+						let fuzz = rng.f32_range(0.0, 0.5);
+						sphere_material = world.add_material(Material::metal(albedo, fuzz));
+						world.add_sphere(Sphere::new(center, 0.2, sphere_material));
+					} else {
+						// glass
+						sphere_material = world.add_material(Material::dielectric(1.5));
+						world.add_sphere(Sphere::new(center, 0.2, sphere_material));
+					}
+				}
+			}
+		}
+
+		let material1 = world.add_material(Material::dielectric(1.5));
+		world.add_sphere(Sphere::new(vec3(0.0, 1.0, 0.0), 1.0, material1));
+
+		let material2 = world.add_material(Material::lambertian(vec3(0.4, 0.2, 0.1)));
+		world.add_sphere(Sphere::new(vec3(-4.0, 1.0, 0.0), 1.0, material2));
+
+		let material3 = world.add_material(Material::metal(vec3(0.7, 0.6, 0.5), 0.0));
+		world.add_sphere(Sphere::new(vec3(4.0, 1.0, 0.0), 1.0, material3));
+
+		world
+	};
+
+	let pitch = 0.0;
+	let yaw = FRAC_PI_2;
+
+	let camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 10,
+		fov: 75.0,
+		location: vec3(13.0, 2.0, 3.0),
+		pitch,
+		yaw,
+		defocus_angle: 0.6,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+
+	(world, camera_parameters)
+}
+
+fn materials(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+
+	let material_ground = world.add_material(Material::lambertian(vec3(0.8, 0.8, 0.0)));
+	let material_center = world.add_material(Material::lambertian(vec3(0.1, 0.2, 0.5)));
+	let material_left = world.add_material(Material::dielectric(1.5));
+	let material_bubble = world.add_material(Material::dielectric(1.0 / 1.5));
+	let material_right = world.add_material(Material::metal(vec3(0.8, 0.6, 0.2), 1.0));
+
+	world.add_sphere(Sphere::new(vec3(0.0, -100.5, -1.0), 100.0, material_ground));
+	world.add_sphere(Sphere::new(vec3(0.0, 0.0, -1.2), 0.5, material_center));
+	world.add_sphere(Sphere::new(vec3(-1.0, 0.0, -1.0), 0.5, material_left));
+	world.add_sphere(Sphere::new(vec3(-1.0, 0.0, -1.0), 0.4, material_bubble));
+	world.add_sphere(Sphere::new(vec3(1.0, 0.0, -1.0), 0.5, material_right));
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 10,
+		fov: 90.0,
+		location: vec3(0.0, 0.0, 0.0),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(vec3(0.0, 0.0, -1.0));
+
+	(world, camera_parameters)
+}
+
+fn sphere_light(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+	world.set_background(Background::Solid(Vec3::ZERO));
+
+	let ground = world.add_material(Material::lambertian(vec3(0.5, 0.5, 0.5)));
+	let matte = world.add_material(Material::lambertian(vec3(0.2, 0.4, 0.8)));
+	let mirror = world.add_material(Material::metal(vec3(0.8, 0.8, 0.8), 0.05));
+	let light = world.add_material(Material::emissive(vec3(1.0, 0.9, 0.8), 4.0));
+
+	world.add_plane(Plane::new(Vec3::ZERO, Vec3::Y, ground));
+	world.add_disk(Disk::new(vec3(1.2, 0.001, 0.0), Vec3::Y, 1.4, matte));
+	world.add_sphere(Sphere::new(vec3(-1.2, 1.0, 0.0), 1.0, matte));
+	world.add_sphere(Sphere::new(vec3(1.2, 1.0, 0.0), 1.0, mirror));
+	world.add_sphere(Sphere::new(vec3(0.0, 4.0, 1.0), 1.0, light));
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 50,
+		fov: 40.0,
+		location: vec3(0.0, 2.5, 8.0),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(vec3(0.0, 1.0, 0.0));
+
+	(world, camera_parameters)
+}
+
+// The Cornell box from "The Next Week" with its two rotated boxes
+fn cornell_box(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+	let white = cornell_walls(&mut world);
+
+	let light = world.add_material(Material::emissive(Vec3::ONE, 15.0));
+	world.add_quad(Quad::new(
+		vec3(343.0, 554.0, 332.0),
+		vec3(-130.0, 0.0, 0.0),
+		vec3(0.0, 0.0, -105.0),
+		light,
+	));
+
+	let tall_box = world.add_object(|world| world.add_box(Vec3::ZERO, vec3(165.0, 330.0, 165.0), white));
+	world.add_instance(tall_box, cornell_tall_box());
+	let short_box = world.add_object(|world| world.add_box(Vec3::ZERO, vec3(165.0, 165.0, 165.0), white));
+	world.add_instance(short_box, cornell_short_box());
+
+	(world, cornell_camera())
+}
+
+// The Cornell box with the boxes turned into smoke and fog, from "The Next Week"
+fn cornell_smoke(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+	cornell_walls(&mut world);
+
+	let light = world.add_material(Material::emissive(Vec3::ONE, 7.0));
+	world.add_quad(Quad::new(
+		vec3(113.0, 554.0, 127.0),
+		vec3(330.0, 0.0, 0.0),
+		vec3(0.0, 0.0, 305.0),
+		light,
+	));
+
+	let smoke = world.add_material(Material::isotropic(Vec3::ZERO));
+	let fog = world.add_material(Material::isotropic(Vec3::ONE));
+	let tall_box = world.add_object(|world| world.add_box(Vec3::ZERO, vec3(165.0, 330.0, 165.0), smoke));
+	world.add_medium(tall_box, cornell_tall_box(), 0.01, smoke);
+	let short_box = world.add_object(|world| world.add_box(Vec3::ZERO, vec3(165.0, 165.0, 165.0), fog));
+	world.add_medium(short_box, cornell_short_box(), 0.01, fog);
+
+	(world, cornell_camera())
+}
+
+// Adds the walls, returns the white material
+fn cornell_walls(world: &mut World) -> MaterialReference {
+	world.set_background(Background::Solid(Vec3::ZERO));
+
+	let red = world.add_material(Material::lambertian(vec3(0.65, 0.05, 0.05)));
+	let white = world.add_material(Material::lambertian(vec3(0.73, 0.73, 0.73)));
+	let green = world.add_material(Material::lambertian(vec3(0.12, 0.45, 0.15)));
+
+	world.add_quad(Quad::new(
+		vec3(555.0, 0.0, 0.0),
+		vec3(0.0, 555.0, 0.0),
+		vec3(0.0, 0.0, 555.0),
+		green,
+	));
+	world.add_quad(Quad::new(
+		vec3(0.0, 0.0, 0.0),
+		vec3(0.0, 555.0, 0.0),
+		vec3(0.0, 0.0, 555.0),
+		red,
+	));
+	world.add_quad(Quad::new(
+		vec3(0.0, 0.0, 0.0),
+		vec3(555.0, 0.0, 0.0),
+		vec3(0.0, 0.0, 555.0),
+		white,
+	));
+	world.add_quad(Quad::new(
+		vec3(555.0, 555.0, 555.0),
+		vec3(-555.0, 0.0, 0.0),
+		vec3(0.0, 0.0, -555.0),
+		white,
+	));
+	world.add_quad(Quad::new(
+		vec3(0.0, 0.0, 555.0),
+		vec3(555.0, 0.0, 0.0),
+		vec3(0.0, 555.0, 0.0),
+		white,
+	));
+
+	white
+}
+
+fn cornell_tall_box() -> Affine3A {
+	Affine3A::from_rotation_translation(Quat::from_rotation_y(15f32.to_radians()), vec3(265.0, 0.0, 295.0))
+}
+
+fn cornell_short_box() -> Affine3A {
+	Affine3A::from_rotation_translation(Quat::from_rotation_y(-18f32.to_radians()), vec3(130.0, 0.0, 65.0))
+}
+
+fn cornell_camera() -> CameraParameters {
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 50,
+		fov: 40.0,
+		location: vec3(278.0, 278.0, -800.0),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(vec3(278.0, 278.0, 0.0));
+
+	camera_parameters
+}
+
+// Two spheres sharing a checker texture from "The Next Week"
+fn checkered_spheres(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+
+	let checker = world.add_texture(Texture::checker(0.32, vec3(0.2, 0.3, 0.1), vec3(0.9, 0.9, 0.9)));
+	let material = world.add_material(Material::lambertian_textured(checker));
+	world.add_sphere(Sphere::new(vec3(0.0, -10.0, 0.0), 10.0, material));
+	world.add_sphere(Sphere::new(vec3(0.0, 10.0, 0.0), 10.0, material));
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 10,
+		fov: 20.0,
+		location: vec3(13.0, 2.0, 3.0),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(Vec3::ZERO);
+
+	(world, camera_parameters)
+}
+
+// Marble ground and a sphere wrapped in a spherical checker
+fn perlin_spheres(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+
+	let marble = world.add_texture(Texture::marble(4.0));
+	let ground = world.add_material(Material::lambertian_textured(marble));
+	world.add_sphere(Sphere::new(vec3(0.0, -1000.0, 0.0), 1000.0, ground));
+
+	let checker = world.add_texture(Texture::spherical_checker(8.0, vec3(0.8, 0.1, 0.1), vec3(0.9, 0.9, 0.9)));
+	let sphere = world.add_material(Material::metal_textured(checker, 0.2));
+	world.add_sphere(Sphere::new(vec3(0.0, 2.0, 0.0), 2.0, sphere));
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 10,
+		fov: 20.0,
+		location: vec3(13.0, 2.0, 3.0),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(vec3(0.0, 1.0, 0.0));
+
+	(world, camera_parameters)
+}
+
+// Rows of gold, red plastic and clearcoated blue paint getting rougher from left to right
+fn microfacet_spheres(_seed: u32) -> (World, CameraParameters) {
+	let mut world = World::new();
+
+	let ground = world.add_material(Material::lambertian(vec3(0.5, 0.5, 0.5)));
+	world.add_plane(Plane::new(Vec3::ZERO, Vec3::Y, ground));
+	let light = world.add_material(Material::emissive(vec3(1.0, 0.9, 0.8), 10.0));
+	world.add_sphere(Sphere::new(vec3(-3.0, 6.0, 4.0), 1.0, light));
+
+	for i in 0..5 {
+		let roughness = i as f32 / 4.0;
+		let x = (i as f32 - 2.0) * 1.2;
+		let gold = world.add_material(Material::microfacet(vec3(1.0, 0.78, 0.34), 1.0, roughness));
+		let plastic = world.add_material(Material::microfacet(vec3(0.8, 0.1, 0.1), 0.0, roughness));
+		let paint = world.add_material(Material::microfacet(vec3(0.1, 0.2, 0.6), 0.0, roughness).with_clearcoat(1.0, 0.03));
+		world.add_sphere(Sphere::new(vec3(x, 0.5, -1.5), 0.5, gold));
+		world.add_sphere(Sphere::new(vec3(x, 0.5, 0.0), 0.5, plastic));
+		world.add_sphere(Sphere::new(vec3(x, 0.5, 1.5), 0.5, paint));
+	}
+
+	let mut camera_parameters = CameraParameters {
+		samples_per_pixel: 10,
+		max_depth: 20,
+		fov: 40.0,
+		location: vec3(0.0, 6.5, 7.5),
+		pitch: 0.0,
+		yaw: 0.0,
+		defocus_angle: 0.0,
+		focus_dist: 10.0,
+		shutter_open: 0.0,
+		shutter_close: 1.0,
+		seed: 0,
+	};
+	camera_parameters.look_at(vec3(0.0, 0.3, 0.0));
+
+	(world, camera_parameters)
+}
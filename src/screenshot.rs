@@ -0,0 +1,147 @@
+use crate::camera::CameraParameters;
+use crate::display::DisplaySettings;
+use crate::image::Image;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Where the interactive scene came from, recorded next to screenshots so they can be re-rendered
+#[derive(Debug, Clone)]
+pub struct SceneSource {
+	// Built-in scene name or absolute path to a scene file
+	pub scene: String,
+	pub seed: Option<u32>,
+}
+
+// Everything needed to reproduce a screenshot, the `camera` and `render` tables use the scene file keys
+#[derive(Serialize)]
+struct Sidecar<'a> {
+	scene: &'a str,
+	seed: Option<u32>,
+	// Renders the same view offline
+	command: String,
+	camera: CameraSidecar,
+	render: RenderSidecar,
+}
+
+#[derive(Serialize)]
+struct CameraSidecar {
+	position: [f32; 3],
+	pitch: f32,
+	yaw: f32,
+	fov: f32,
+	defocus_angle: f32,
+	focus_dist: f32,
+	shutter: [f32; 2],
+}
+
+#[derive(Serialize)]
+struct RenderSidecar {
+	samples_per_pixel: u32,
+	max_depth: u32,
+	width: u32,
+	height: u32,
+	// Frames accumulated when the screenshot was taken
+	passes: u32,
+	tone_mapper: &'static str,
+	exposure: f32,
+	dither: bool,
+	seed: u32,
+}
+
+// Writes `screenshot-<unix time in ms>.png` and a .toml sidecar into the working directory, returns the image path
+pub fn save(
+	image: &Image,
+	source: &SceneSource,
+	parameters: CameraParameters,
+	display: DisplaySettings,
+	passes: u32,
+) -> io::Result<PathBuf> {
+	let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+	let path = PathBuf::from(format!("screenshot-{timestamp}.png"));
+	image.write_png(&path)?;
+
+	let p = parameters;
+	let sidecar = Sidecar {
+		scene: &source.scene,
+		seed: source.seed,
+		command: command(image, source, parameters, display, passes, &path),
+		camera: CameraSidecar {
+			position: p.location.to_array(),
+			pitch: p.pitch,
+			yaw: p.yaw,
+			fov: p.fov,
+			defocus_angle: p.defocus_angle,
+			focus_dist: p.focus_dist,
+			shutter: [p.shutter_open, p.shutter_close],
+		},
+		render: RenderSidecar {
+			samples_per_pixel: p.samples_per_pixel,
+			max_depth: p.max_depth,
+			width: image.width,
+			height: image.height,
+			passes,
+			tone_mapper: display.tone_mapper.name(),
+			exposure: display.exposure,
+			dither: display.dither,
+			seed: p.seed,
+		},
+	};
+	let contents = toml::to_string(&sidecar).map_err(io::Error::other)?;
+	std::fs::write(path.with_extension("toml"), contents)?;
+
+	Ok(path)
+}
+
+// Floats are printed with the shortest representation that parses back to the same value
+fn command(
+	image: &Image,
+	source: &SceneSource,
+	p: CameraParameters,
+	display: DisplaySettings,
+	passes: u32,
+	path: &Path,
+) -> String {
+	let mut command = format!("{} --scene {}", env!("CARGO_PKG_NAME"), quote(&source.scene));
+	if let Some(seed) = source.seed {
+		write!(command, " --seed {seed}").unwrap();
+	}
+	let position = p.location;
+	write!(
+		command,
+		" --width {} --height {} --spp {} --max-depth {} --fov {} --camera-pos {},{},{} --pitch {} --yaw {} \
+		 --defocus-angle {} --focus-dist {} --tone-mapper {} --exposure {}",
+		image.width,
+		image.height,
+		p.samples_per_pixel,
+		p.max_depth,
+		p.fov,
+		position.x,
+		position.y,
+		position.z,
+		p.pitch,
+		p.yaw,
+		p.defocus_angle,
+		p.focus_dist,
+		display.tone_mapper.name(),
+		display.exposure,
+	)
+	.unwrap();
+	if display.dither {
+		command.push_str(" --dither");
+	}
+	let output = path.with_file_name(format!("{}-offline.png", path.file_stem().unwrap().to_string_lossy()));
+	write!(command, " render -o {} --passes {passes}", quote(&output.to_string_lossy())).unwrap();
+	command
+}
+
+// POSIX shell quoting for anything beyond plain path characters
+fn quote(argument: &str) -> String {
+	if !argument.is_empty() && argument.chars().all(|c| c.is_ascii_alphanumeric() || "-_./,".contains(c)) {
+		argument.to_owned()
+	} else {
+		format!("'{}'", argument.replace('\'', r"'\''"))
+	}
+}
@@ -1,25 +1,34 @@
+use crate::bvh::Aabb;
 use crate::material::MaterialReference;
 use glam::Vec3;
 
 #[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 #[repr(C)]
 pub struct Sphere {
+	// Center at time 0, see `CameraParameters::shutter_open`
 	pub center: Vec3,
 	pub radius: f32,
+	// Displacement of the center from time 0 to time 1
+	pub motion: Vec3,
 	pub material: MaterialReference,
-	pub _p0: u32,
-	pub _p1: u32,
-	pub _p2: u32,
 }
 impl Sphere {
 	pub fn new(center: Vec3, radius: f32, material: MaterialReference) -> Self {
+		Self::moving(center, center, radius, material)
+	}
+	// Moves linearly from `start` at time 0 to `end` at time 1
+	pub fn moving(start: Vec3, end: Vec3, radius: f32, material: MaterialReference) -> Self {
 		Self {
-			center,
+			center: start,
 			radius,
+			motion: end - start,
 			material,
-			_p0: 0,
-			_p1: 0,
-			_p2: 0,
 		}
 	}
+	// Covers the whole path from time 0 to 1
+	pub fn bounds(&self) -> Aabb {
+		let radius = Vec3::splat(self.radius.abs());
+		let end = self.center + self.motion;
+		Aabb::new(self.center.min(end) - radius, self.center.max(end) + radius)
+	}
 }
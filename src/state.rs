@@ -1,205 +1,77 @@
 use crate::app::ControlMap;
-use crate::camera::{Camera, CameraParameters};
+use crate::camera::{CameraParameters, MAX_DEFOCUS_ANGLE};
+use crate::display::DisplaySettings;
+use crate::gpu::{self, AdapterOptions, GpuError};
+use crate::screenshot::{self, SceneSource};
+use crate::tracer::Tracer;
 use crate::world::World;
 use glam::vec3;
 use std::sync::Arc;
-use wesl::include_wesl;
-use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-	Backends, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
-	BindingType, BlendComponent, BlendState, BufferBindingType, BufferUsages, Color, ColorTargetState, ColorWrites,
-	CompositeAlphaMode, Device, DeviceDescriptor, Face, FragmentState, FrontFace, Instance, InstanceDescriptor, LoadOp,
-	MultisampleState, Operations, PipelineLayoutDescriptor, PolygonMode, PresentMode, PrimitiveState,
-	PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
-	RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource, ShaderStages, StoreOp,
-	Surface, SurfaceConfiguration, TextureFormat, TextureUsages, TextureViewDescriptor, VertexState,
+	CompositeAlphaMode, Device, PresentMode, Queue, Surface, SurfaceConfiguration, TextureFormat, TextureUsages,
+	TextureViewDescriptor,
 };
 use winit::dpi::PhysicalSize;
 use winit::window::{CursorGrabMode, Window};
 
-pub struct State {
+// Renders into a window's surface, progressively refining the image while the camera is still
+pub struct WindowRenderer {
 	window: Arc<Window>,
 	device: Device,
 	queue: Queue,
 	size: PhysicalSize<u32>,
 	surface: Surface<'static>,
 	surface_format: TextureFormat,
-	pipeline: RenderPipeline,
-	camera: Camera,
-	bind_group: BindGroup,
+	tracer: Tracer,
+	source: SceneSource,
 	is_mouse_focused: bool,
 }
 
-impl State {
-	pub async fn new(window: Arc<Window>, camera_parameters: CameraParameters, world: &World) -> State {
-		let instance = Instance::new(&InstanceDescriptor {
-			backends: Backends::DX12,
-			..Default::default()
-		});
-		let adapter = instance
-			.request_adapter(&RequestAdapterOptions::default())
-			.await
-			.unwrap();
-		let (device, queue) = adapter.request_device(&DeviceDescriptor::default()).await.unwrap();
+impl WindowRenderer {
+	pub async fn new(
+		window: Arc<Window>,
+		camera_parameters: CameraParameters,
+		display: DisplaySettings,
+		source: SceneSource,
+		world: &World,
+		adapter_options: &AdapterOptions,
+	) -> Result<Self, GpuError> {
+		let instance = adapter_options.create_instance();
+		let surface = instance.create_surface(window.clone()).unwrap();
+		let adapter = adapter_options.request_adapter(&instance, Some(&surface)).await?;
+		let (device, queue) = gpu::request_device(&adapter, Tracer::required_limits()).await?;
 
 		let size = window.inner_size();
 
-		let surface = instance.create_surface(window.clone()).unwrap();
 		let cap = surface.get_capabilities(&adapter);
 		let surface_format = cap.formats[0];
 
-		let camera = Camera::new(&device, camera_parameters, size.width, size.height);
-
-		let sphere_buffer = device.create_buffer_init(&BufferInitDescriptor {
-			label: Some("Sphere Buffer"),
-			contents: bytemuck::cast_slice(world.spheres()),
-			usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-		});
-
-		let material_buffer = device.create_buffer_init(&BufferInitDescriptor {
-			label: Some("Material Buffer"),
-			contents: bytemuck::cast_slice(world.materials()),
-			usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
-		});
-
-		let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-			label: None,
-			entries: &[
-				BindGroupLayoutEntry {
-					binding: 0,
-					visibility: ShaderStages::FRAGMENT,
-					ty: BindingType::Buffer {
-						ty: BufferBindingType::Uniform,
-						has_dynamic_offset: false,
-						min_binding_size: None,
-					},
-					count: None,
-				},
-				BindGroupLayoutEntry {
-					binding: 1,
-					visibility: ShaderStages::FRAGMENT,
-					ty: BindingType::Buffer {
-						ty: BufferBindingType::Storage { read_only: true },
-						has_dynamic_offset: false,
-						min_binding_size: None,
-					},
-					count: None,
-				},
-				BindGroupLayoutEntry {
-					binding: 2,
-					visibility: ShaderStages::FRAGMENT,
-					ty: BindingType::Buffer {
-						ty: BufferBindingType::Storage { read_only: true },
-						has_dynamic_offset: false,
-						min_binding_size: None,
-					},
-					count: None,
-				},
-			],
-		});
-
-		let bind_group = device.create_bind_group(&BindGroupDescriptor {
-			layout: &bind_group_layout,
-			entries: &[
-				BindGroupEntry {
-					binding: 0,
-					resource: camera.binding(),
-				},
-				BindGroupEntry {
-					binding: 1,
-					resource: sphere_buffer.as_entire_binding(),
-				},
-				BindGroupEntry {
-					binding: 2,
-					resource: material_buffer.as_entire_binding(),
-				},
-			],
-			label: Some("Bind Group"),
-		});
-
-		let pipeline = {
-			let fragment_shader = device.create_shader_module(ShaderModuleDescriptor {
-				label: Some("Fragment Shader"),
-				source: ShaderSource::Wgsl(include_wesl!("fragment").into()),
-			});
-			let vertex_shader = device.create_shader_module(ShaderModuleDescriptor {
-				label: Some("Vertex Shader"),
-				source: ShaderSource::Wgsl(include_wesl!("vertex").into()),
-			});
-
-			let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-				label: Some("Render Pipeline Layout"),
-				bind_group_layouts: &[&bind_group_layout],
-				push_constant_ranges: &[],
-			});
-
-			let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-				label: Some("Render Pipeline"),
-				layout: Some(&render_pipeline_layout),
-				vertex: VertexState {
-					module: &vertex_shader,
-					entry_point: Some("vs_main"),
-					buffers: &[],
-					compilation_options: Default::default(),
-				},
-				fragment: Some(FragmentState {
-					module: &fragment_shader,
-					entry_point: Some("fs_main"),
-					targets: &[Some(ColorTargetState {
-						format: surface_format,
-						blend: Some(BlendState {
-							color: BlendComponent::REPLACE,
-							alpha: BlendComponent::REPLACE,
-						}),
-						write_mask: ColorWrites::ALL,
-					})],
-					compilation_options: Default::default(),
-				}),
-				primitive: PrimitiveState {
-					topology: PrimitiveTopology::TriangleList,
-					strip_index_format: None,
-					front_face: FrontFace::Ccw,
-					cull_mode: Some(Face::Back),
-					// Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
-					// or Features::POLYGON_MODE_POINT
-					polygon_mode: PolygonMode::Fill,
-					// Requires Features::DEPTH_CLIP_CONTROL
-					unclipped_depth: false,
-					// Requires Features::CONSERVATIVE_RASTERIZATION
-					conservative: false,
-				},
-				depth_stencil: None,
-				multisample: MultisampleState {
-					count: 1,
-					mask: !0,
-					alpha_to_coverage_enabled: false,
-				},
-				// If the pipeline will be used with a multiview render pass, this
-				// indicates how many array layers the attachments will have.
-				multiview: None,
-				// Useful for optimizing shader compilation on Android
-				cache: None,
-			});
-
-			render_pipeline
-		};
-
-		let state = State {
+		let mut tracer = Tracer::new(
+			&device,
+			&queue,
+			surface_format.add_srgb_suffix(),
+			world,
+			camera_parameters,
+			size.width,
+			size.height,
+		);
+		tracer.set_display(&queue, display);
+
+		let state = WindowRenderer {
 			window,
 			device,
 			queue,
 			size,
 			surface,
 			surface_format,
-			pipeline,
-			camera,
-			bind_group,
+			tracer,
+			source,
 			is_mouse_focused: false,
 		};
 
 		state.configure_surface();
 
-		state
+		Ok(state)
 	}
 
 	fn configure_surface(&self) {
@@ -225,9 +97,8 @@ impl State {
 
 		if self.size.width > 0 && self.size.height > 0 {
 			self.configure_surface();
-			self.camera.width = self.size.width;
-			self.camera.height = self.size.height;
-			self.camera.update_buffer(&self.queue);
+			self.tracer
+				.resize(&self.device, &self.queue, self.size.width, self.size.height);
 		}
 	}
 
@@ -235,37 +106,92 @@ impl State {
 		let zoom_speed = 10.0;
 		let sensitivity = 0.005;
 		let movement_speed = 5.0;
+		// Degrees per second
+		let aperture_speed = 1.0;
+		// Relative change per second
+		let focus_speed = 0.5;
+		// Keeps the lens within what `check_defocus_angle` and `check_focus_dist` accept
+		let max_defocus_angle = MAX_DEFOCUS_ANGLE - 1.0;
+		let (min_focus_dist, max_focus_dist) = (0.001, 1.0e6);
+		// Stops per second
+		let exposure_speed = 1.0;
+
+		// Before anything changes, so the sidecar describes the image. Waits for a frame to be accumulated after the
+		// camera moved, the texture is empty until then.
+		if control_map.screenshot && self.tracer.camera.frame > 0 {
+			control_map.screenshot = false;
+			self.screenshot();
+		}
+
+		let mut display = self.tracer.display();
+		if control_map.next_tone_mapper {
+			control_map.next_tone_mapper = false;
+			display.tone_mapper = display.tone_mapper.next();
+			log::info!("Tone mapper: {:?}", display.tone_mapper);
+		}
+		if control_map.brighter {
+			display.exposure += exposure_speed * delta_time;
+		} else if control_map.darker {
+			display.exposure -= exposure_speed * delta_time;
+		}
+		if display != self.tracer.display() {
+			self.tracer.set_display(&self.queue, display);
+		}
+
+		let previous_parameters = self.tracer.camera.parameters;
+
+		if control_map.auto_focus {
+			control_map.auto_focus = false;
+			if let Some(distance) = self.tracer.focus_distance(&self.device, &self.queue) {
+				self.tracer.camera.parameters.focus_dist = distance;
+			}
+		}
+		let parameters = &mut self.tracer.camera.parameters;
+		if control_map.aperture_wider {
+			parameters.defocus_angle = (parameters.defocus_angle + aperture_speed * delta_time).min(max_defocus_angle);
+		} else if control_map.aperture_narrower {
+			parameters.defocus_angle = (parameters.defocus_angle - aperture_speed * delta_time).max(0.0);
+		}
+		if control_map.focus_farther {
+			parameters.focus_dist = (parameters.focus_dist * (1.0 + focus_speed * delta_time)).min(max_focus_dist);
+		} else if control_map.focus_nearer {
+			parameters.focus_dist = (parameters.focus_dist / (1.0 + focus_speed * delta_time)).max(min_focus_dist);
+		}
 
 		if control_map.zoom_in {
-			self.camera.parameters.fov -= zoom_speed * delta_time;
+			self.tracer.camera.parameters.fov -= zoom_speed * delta_time;
 		} else if control_map.zoom_out {
-			self.camera.parameters.fov += zoom_speed * delta_time;
+			self.tracer.camera.parameters.fov += zoom_speed * delta_time;
 		}
 
-		self.camera.parameters.pitch += control_map.move_pitch * sensitivity;
-		self.camera.parameters.yaw -= control_map.move_yaw * sensitivity;
+		self.tracer.camera.parameters.pitch += control_map.move_pitch * sensitivity;
+		self.tracer.camera.parameters.yaw -= control_map.move_yaw * sensitivity;
 		control_map.move_pitch = 0.0;
 		control_map.move_yaw = 0.0;
 
-		let ys = self.camera.parameters.yaw.sin();
-		let yc = self.camera.parameters.yaw.cos();
+		let ys = self.tracer.camera.parameters.yaw.sin();
+		let yc = self.tracer.camera.parameters.yaw.cos();
 
 		let backward = vec3(ys, 0.0, yc);
 		let up = vec3(0.0, 1.0, 0.0);
 		let left = backward.cross(up);
 
 		if control_map.move_forward {
-			self.camera.parameters.location -= backward * movement_speed * delta_time;
+			self.tracer.camera.parameters.location -= backward * movement_speed * delta_time;
 		} else if control_map.move_backward {
-			self.camera.parameters.location += backward * movement_speed * delta_time;
+			self.tracer.camera.parameters.location += backward * movement_speed * delta_time;
 		}
 		if control_map.move_left {
-			self.camera.parameters.location += left * movement_speed * delta_time;
+			self.tracer.camera.parameters.location += left * movement_speed * delta_time;
 		} else if control_map.move_right {
-			self.camera.parameters.location -= left * movement_speed * delta_time;
+			self.tracer.camera.parameters.location -= left * movement_speed * delta_time;
 		}
 
-		self.camera.update_buffer(&self.queue);
+		if self.tracer.camera.parameters != previous_parameters {
+			self.tracer.camera.frame = 0;
+		}
+
+		self.tracer.camera.update_buffer(&self.queue);
 	}
 
 	pub fn render(&mut self) {
@@ -282,33 +208,34 @@ impl State {
 
 		let mut encoder = self.device.create_command_encoder(&Default::default());
 
-		{
-			let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-				label: None,
-				color_attachments: &[Some(RenderPassColorAttachment {
-					view: &texture_view,
-					resolve_target: None,
-					ops: Operations {
-						load: LoadOp::Clear(Color::GREEN),
-						store: StoreOp::Store,
-					},
-				})],
-				depth_stencil_attachment: None,
-				timestamp_writes: None,
-				occlusion_query_set: None,
-			});
-
-			render_pass.set_bind_group(0, &self.bind_group, &[]);
-
-			render_pass.set_pipeline(&self.pipeline);
-			render_pass.draw(0..3, 0..1);
-		}
+		self.tracer.render(&mut encoder, &texture_view);
 
 		self.queue.submit([encoder.finish()]);
 		self.window.pre_present_notify();
 		surface_texture.present();
 	}
 
+	fn screenshot(&self) {
+		let image = match self.tracer.capture(&self.device, &self.queue) {
+			Ok(image) => image,
+			Err(e) => {
+				log::error!("Failed to take screenshot: {e}");
+				return;
+			},
+		};
+		let result = screenshot::save(
+			&image,
+			&self.source,
+			self.tracer.camera.parameters,
+			self.tracer.display(),
+			self.tracer.camera.frame,
+		);
+		match result {
+			Ok(path) => log::info!("Saved screenshot to {}", path.display()),
+			Err(e) => log::error!("Failed to save screenshot: {e}"),
+		}
+	}
+
 	pub fn focus(&mut self) {
 		let result = self.window.set_cursor_grab(CursorGrabMode::Confined);
 		match result {
@@ -319,11 +246,13 @@ impl State {
 		}
 		self.window.set_cursor_visible(false);
 		self.is_mouse_focused = true;
+		self.tracer.set_crosshair(&self.queue, true);
 	}
 	pub fn unfocus(&mut self) {
 		self.window.set_cursor_grab(CursorGrabMode::None).unwrap();
 		self.window.set_cursor_visible(true);
 		self.is_mouse_focused = false;
+		self.tracer.set_crosshair(&self.queue, false);
 	}
 	pub fn is_mouse_focused(&self) -> bool {
 		self.is_mouse_focused
@@ -0,0 +1,90 @@
+use glam::Vec3;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct TextureReference {
+	id: u32,
+}
+impl TextureReference {
+	pub fn new(id: u32) -> Self {
+		Self { id }
+	}
+	pub fn id(&self) -> u32 {
+		self.id
+	}
+}
+
+// `Material::texture` of materials that use their constant albedo instead
+pub const NO_TEXTURE: u32 = u32::MAX;
+
+// Must match the constants in texture.wesl
+pub(crate) const SOLID: u32 = 0;
+pub(crate) const CHECKER: u32 = 1;
+pub(crate) const SPHERICAL_CHECKER: u32 = 2;
+pub(crate) const NOISE: u32 = 3;
+pub(crate) const TURBULENCE: u32 = 4;
+pub(crate) const MARBLE: u32 = 5;
+pub(crate) const IMAGE: u32 = 6;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+pub struct Texture {
+	// Colour of solid textures and the even cells of checkers, tint of the noise textures
+	pub(crate) color: Vec3,
+	// Cell size of `checker`, cells per half turn of `spherical_checker`, frequency of the noise textures
+	pub(crate) scale: f32,
+	pub(crate) odd: Vec3,
+	pub(crate) texture_type: u32,
+	// Layer of the image array, see `World::add_image`
+	pub(crate) layer: u32,
+	_p0: u32,
+	_p1: u32,
+	_p2: u32,
+}
+impl Texture {
+	fn new(texture_type: u32, color: Vec3, scale: f32, odd: Vec3) -> Self {
+		Self {
+			color,
+			scale,
+			odd,
+			texture_type,
+			layer: 0,
+			_p0: 0,
+			_p1: 0,
+			_p2: 0,
+		}
+	}
+	pub fn solid(color: Vec3) -> Self {
+		Self::new(SOLID, color, 1.0, Vec3::ZERO)
+	}
+	// Cubes of edge length `scale` in space
+	pub fn checker(scale: f32, even: Vec3, odd: Vec3) -> Self {
+		Self::new(CHECKER, even, scale, odd)
+	}
+	// Checker in texture coordinates, `cells` along v and twice as many along u so they're square around a sphere's
+	// equator
+	pub fn spherical_checker(cells: f32, even: Vec3, odd: Vec3) -> Self {
+		Self::new(SPHERICAL_CHECKER, even, cells, odd)
+	}
+	// Perlin noise mapped to [0, 1]
+	pub fn noise(scale: f32) -> Self {
+		Self::new(NOISE, Vec3::ONE, scale, Vec3::ZERO)
+	}
+	// Sum of seven octaves of Perlin noise
+	pub fn turbulence(scale: f32) -> Self {
+		Self::new(TURBULENCE, Vec3::ONE, scale, Vec3::ZERO)
+	}
+	// Sine stripes along z, phase shifted by turbulence
+	pub fn marble(scale: f32) -> Self {
+		Self::new(MARBLE, Vec3::ONE, scale, Vec3::ZERO)
+	}
+	pub fn image(layer: u32) -> Self {
+		Self {
+			layer,
+			..Self::new(IMAGE, Vec3::ONE, 1.0, Vec3::ZERO)
+		}
+	}
+	pub fn with_color(self, color: Vec3) -> Self {
+		Self { color, ..self }
+	}
+}
@@ -0,0 +1,968 @@
+#[cfg(test)]
+mod shader_layout;
+
+use crate::camera::{Camera, CameraParameters};
+use crate::display::DisplaySettings;
+use crate::environment;
+use crate::image::Image;
+use crate::layout::SceneLayout;
+use crate::world::World;
+use image::imageops::{self, FilterType};
+use image::{Rgba32FImage, RgbaImage};
+use std::fmt;
+use std::ops::Range;
+use std::sync::mpsc;
+use wesl::include_wesl;
+use wgpu::util::{BufferInitDescriptor, DeviceExt, TextureDataOrder};
+use wgpu::{
+	AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+	BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent, BlendState, Buffer, BufferAddress,
+	BufferBinding, BufferBindingType, BufferDescriptor, BufferSlice, BufferSize, BufferUsages, Color, ColorTargetState,
+	ColorWrites, CommandEncoder, ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Device, Extent3d,
+	Face, FilterMode, FragmentState, FrontFace, Limits, LoadOp, MapMode, MultisampleState, Operations, Origin3d,
+	PipelineLayoutDescriptor, PollType, PolygonMode, PrimitiveState, PrimitiveTopology, Queue,
+	RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler,
+	SamplerBindingType, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, StorageTextureAccess,
+	StoreOp, TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo, Texture, TextureAspect,
+	TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+	TextureViewDescriptor, TextureViewDimension, VertexState, COPY_BYTES_PER_ROW_ALIGNMENT,
+};
+
+// Edge length in pixels of the tiles dispatched by `Tracer::trace_tiles`
+pub const TILE_SIZE: u32 = 64;
+// Must match `@workgroup_size` in trace.wesl
+const WORKGROUP_SIZE: u32 = 8;
+const OUTPUT_FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct TileUniform {
+	origin: [u32; 2],
+	size: [u32; 2],
+}
+
+#[derive(Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+#[repr(C)]
+struct BlitUniform {
+	// Linear scale
+	exposure: f32,
+	tone_mapper: u32,
+	dither: u32,
+	crosshair: u32,
+	// Set for targets without an sRGB format, which need the shader to encode the output
+	encode_srgb: u32,
+	_p0: u32,
+	_p1: u32,
+	_p2: u32,
+}
+impl BlitUniform {
+	fn new(display: DisplaySettings, crosshair: bool, target_format: TextureFormat) -> Self {
+		Self {
+			exposure: display.exposure.exp2(),
+			tone_mapper: display.tone_mapper.id(),
+			dither: display.dither as u32,
+			crosshair: crosshair as u32,
+			encode_srgb: !target_format.is_srgb() as u32,
+			_p0: 0,
+			_p1: 0,
+			_p2: 0,
+		}
+	}
+}
+
+struct SceneBuffers {
+	spheres: Buffer,
+	materials: Buffer,
+	bvh_nodes: Buffer,
+	background: Buffer,
+	lights: Buffer,
+	vertices: Buffer,
+	triangles: Buffer,
+	primitives: Buffer,
+	quads: Buffer,
+	planes: Buffer,
+	disks: Buffer,
+	instances: Buffer,
+	textures: Buffer,
+	images: TextureView,
+	image_sampler: Sampler,
+	environment_map: TextureView,
+	environment_distribution: Buffer,
+}
+
+// Everything that depends on the image size
+struct FrameResources {
+	width: u32,
+	height: u32,
+	tiles_x: u32,
+	tiles_y: u32,
+	_accumulation_buffer: Buffer,
+	_output_texture: Texture,
+	_tile_buffer: Buffer,
+	// Written by `Tracer::focus_distance`
+	focus_buffer: Buffer,
+	trace_bind_group: BindGroup,
+	blit_bind_group: BindGroup,
+}
+
+pub struct Tracer {
+	pub camera: Camera,
+	trace_pipeline: ComputePipeline,
+	focus_pipeline: ComputePipeline,
+	blit_pipeline: RenderPipeline,
+	trace_bind_group_layout: BindGroupLayout,
+	blit_bind_group_layout: BindGroupLayout,
+	scene_buffers: SceneBuffers,
+	blit_buffer: Buffer,
+	target_format: TextureFormat,
+	display: DisplaySettings,
+	crosshair: bool,
+	// Distance between consecutive `TileUniform`s, respecting the dynamic offset alignment
+	tile_stride: u32,
+	frame_resources: FrameResources,
+}
+
+impl Tracer {
+	// Every primitive type has its own storage buffer, which is more than the 8 WebGPU guarantees. Desktop adapters
+	// support them, `gpu::request_device` names the limits an adapter falls short of.
+	pub fn required_limits() -> Limits {
+		Limits {
+			max_storage_buffers_per_shader_stage: 15,
+			..Limits::default()
+		}
+	}
+
+	pub fn new(
+		device: &Device,
+		queue: &Queue,
+		target_format: TextureFormat,
+		world: &World,
+		camera_parameters: CameraParameters,
+		width: u32,
+		height: u32,
+	) -> Self {
+		let camera = Camera::new(device, camera_parameters, width, height);
+
+		let SceneLayout {
+			nodes,
+			primitives,
+			instances,
+			lights,
+		} = SceneLayout::new(world);
+
+		// Light count followed by the light indices, with one padding entry so the buffer is never smaller than the
+		// binding in trace.wesl
+		let light_data = std::iter::once(lights.len() as u32)
+			.chain(lights)
+			.chain(std::iter::once(0))
+			.collect::<Vec<_>>();
+
+		let scene_buffers = SceneBuffers {
+			spheres: storage_buffer_init(device, "Sphere Buffer", world.spheres()),
+			materials: storage_buffer_init(device, "Material Buffer", world.materials()),
+			bvh_nodes: storage_buffer_init(device, "BVH Buffer", &nodes),
+			vertices: storage_buffer_init(device, "Vertex Buffer", world.vertices()),
+			triangles: storage_buffer_init(device, "Triangle Buffer", world.triangles()),
+			primitives: storage_buffer_init(device, "Primitive Buffer", &primitives),
+			quads: storage_buffer_init(device, "Quad Buffer", world.quads()),
+			planes: storage_buffer_init(device, "Plane Buffer", world.planes()),
+			disks: storage_buffer_init(device, "Disk Buffer", world.disks()),
+			instances: storage_buffer_init(device, "Instance Buffer", &instances),
+			textures: storage_buffer_init(device, "Texture Buffer", world.textures()),
+			images: create_image_array(device, queue, world.images()),
+			image_sampler: device.create_sampler(&SamplerDescriptor {
+				label: Some("Image Sampler"),
+				address_mode_u: AddressMode::Repeat,
+				address_mode_v: AddressMode::Repeat,
+				mag_filter: FilterMode::Linear,
+				min_filter: FilterMode::Linear,
+				..Default::default()
+			}),
+			background: device.create_buffer_init(&BufferInitDescriptor {
+				label: Some("Background Uniform Buffer"),
+				contents: bytemuck::bytes_of(&world.background().uniform(world.surface_fog())),
+				usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+			}),
+			lights: storage_buffer_init(device, "Light Buffer", &light_data),
+			environment_map: create_environment_map(device, queue, world.environment_map()),
+			environment_distribution: storage_buffer_init(
+				device,
+				"Environment Distribution Buffer",
+				&world.environment_map().map_or_else(Vec::new, environment::distribution),
+			),
+		};
+
+		let uniform_buffer = |binding| BindGroupLayoutEntry {
+			binding,
+			visibility: ShaderStages::COMPUTE,
+			ty: BindingType::Buffer {
+				ty: BufferBindingType::Uniform,
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+		let storage_buffer = |binding, read_only| BindGroupLayoutEntry {
+			binding,
+			visibility: ShaderStages::COMPUTE,
+			ty: BindingType::Buffer {
+				ty: BufferBindingType::Storage { read_only },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+
+		let trace_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+			label: Some("Trace Bind Group Layout"),
+			entries: &[
+				uniform_buffer(0),
+				storage_buffer(1, true),
+				storage_buffer(2, true),
+				storage_buffer(3, false),
+				storage_buffer(4, true),
+				BindGroupLayoutEntry {
+					binding: 5,
+					visibility: ShaderStages::COMPUTE,
+					ty: BindingType::StorageTexture {
+						access: StorageTextureAccess::WriteOnly,
+						format: OUTPUT_FORMAT,
+						view_dimension: TextureViewDimension::D2,
+					},
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 6,
+					visibility: ShaderStages::COMPUTE,
+					ty: BindingType::Buffer {
+						ty: BufferBindingType::Uniform,
+						has_dynamic_offset: true,
+						min_binding_size: BufferSize::new(size_of::<TileUniform>() as u64),
+					},
+					count: None,
+				},
+				uniform_buffer(7),
+				storage_buffer(8, true),
+				storage_buffer(9, true),
+				storage_buffer(10, true),
+				storage_buffer(11, true),
+				storage_buffer(12, true),
+				storage_buffer(13, true),
+				storage_buffer(14, true),
+				storage_buffer(15, true),
+				storage_buffer(16, true),
+				BindGroupLayoutEntry {
+					binding: 17,
+					visibility: ShaderStages::COMPUTE,
+					ty: BindingType::Texture {
+						sample_type: TextureSampleType::Float { filterable: true },
+						view_dimension: TextureViewDimension::D2Array,
+						multisampled: false,
+					},
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 18,
+					visibility: ShaderStages::COMPUTE,
+					ty: BindingType::Sampler(SamplerBindingType::Filtering),
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 19,
+					visibility: ShaderStages::COMPUTE,
+					ty: BindingType::Texture {
+						sample_type: TextureSampleType::Float { filterable: false },
+						view_dimension: TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				storage_buffer(20, true),
+				storage_buffer(21, false),
+			],
+		});
+
+		let blit_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+			label: Some("Blit Bind Group Layout"),
+			entries: &[
+				BindGroupLayoutEntry {
+					binding: 0,
+					visibility: ShaderStages::FRAGMENT,
+					ty: BindingType::Texture {
+						sample_type: TextureSampleType::Float { filterable: false },
+						view_dimension: TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				BindGroupLayoutEntry {
+					binding: 1,
+					visibility: ShaderStages::FRAGMENT,
+					ty: BindingType::Buffer {
+						ty: BufferBindingType::Uniform,
+						has_dynamic_offset: false,
+						min_binding_size: None,
+					},
+					count: None,
+				},
+			],
+		});
+		let blit_buffer = device.create_buffer_init(&BufferInitDescriptor {
+			label: Some("Blit Uniform Buffer"),
+			contents: bytemuck::bytes_of(&BlitUniform::new(DisplaySettings::default(), false, target_format)),
+			usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+		});
+
+		let (trace_pipeline, focus_pipeline) = {
+			let trace_shader = device.create_shader_module(ShaderModuleDescriptor {
+				label: Some("Trace Shader"),
+				source: ShaderSource::Wgsl(include_wesl!("trace").into()),
+			});
+
+			let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+				label: Some("Trace Pipeline Layout"),
+				bind_group_layouts: &[&trace_bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+			let pipeline = |label, entry_point| {
+				device.create_compute_pipeline(&ComputePipelineDescriptor {
+					label: Some(label),
+					layout: Some(&layout),
+					module: &trace_shader,
+					entry_point: Some(entry_point),
+					compilation_options: Default::default(),
+					cache: None,
+				})
+			};
+			(pipeline("Trace Pipeline", "cs_main"), pipeline("Focus Pipeline", "cs_focus"))
+		};
+
+		let blit_pipeline = {
+			let fragment_shader = device.create_shader_module(ShaderModuleDescriptor {
+				label: Some("Blit Shader"),
+				source: ShaderSource::Wgsl(include_wesl!("blit").into()),
+			});
+			let vertex_shader = device.create_shader_module(ShaderModuleDescriptor {
+				label: Some("Vertex Shader"),
+				source: ShaderSource::Wgsl(include_wesl!("vertex").into()),
+			});
+
+			let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+				label: Some("Blit Pipeline Layout"),
+				bind_group_layouts: &[&blit_bind_group_layout],
+				push_constant_ranges: &[],
+			});
+
+			device.create_render_pipeline(&RenderPipelineDescriptor {
+				label: Some("Blit Pipeline"),
+				layout: Some(&render_pipeline_layout),
+				vertex: VertexState {
+					module: &vertex_shader,
+					entry_point: Some("vs_main"),
+					buffers: &[],
+					compilation_options: Default::default(),
+				},
+				fragment: Some(FragmentState {
+					module: &fragment_shader,
+					entry_point: Some("fs_main"),
+					targets: &[Some(ColorTargetState {
+						format: target_format,
+						blend: Some(BlendState {
+							color: BlendComponent::REPLACE,
+							alpha: BlendComponent::REPLACE,
+						}),
+						write_mask: ColorWrites::ALL,
+					})],
+					compilation_options: Default::default(),
+				}),
+				primitive: PrimitiveState {
+					topology: PrimitiveTopology::TriangleList,
+					strip_index_format: None,
+					front_face: FrontFace::Ccw,
+					cull_mode: Some(Face::Back),
+					// Setting this to anything other than Fill requires Features::POLYGON_MODE_LINE
+					// or Features::POLYGON_MODE_POINT
+					polygon_mode: PolygonMode::Fill,
+					// Requires Features::DEPTH_CLIP_CONTROL
+					unclipped_depth: false,
+					// Requires Features::CONSERVATIVE_RASTERIZATION
+					conservative: false,
+				},
+				depth_stencil: None,
+				multisample: MultisampleState {
+					count: 1,
+					mask: !0,
+					alpha_to_coverage_enabled: false,
+				},
+				// If the pipeline will be used with a multiview render pass, this
+				// indicates how many array layers the attachments will have.
+				multiview: None,
+				// Useful for optimizing shader compilation on Android
+				cache: None,
+			})
+		};
+
+		let tile_stride =
+			(size_of::<TileUniform>() as u32).next_multiple_of(device.limits().min_uniform_buffer_offset_alignment);
+
+		let frame_resources = create_frame_resources(
+			device,
+			&trace_bind_group_layout,
+			&blit_bind_group_layout,
+			&scene_buffers,
+			&blit_buffer,
+			&camera,
+			tile_stride,
+			width,
+			height,
+		);
+
+		Self {
+			camera,
+			trace_pipeline,
+			focus_pipeline,
+			blit_pipeline,
+			trace_bind_group_layout,
+			blit_bind_group_layout,
+			scene_buffers,
+			blit_buffer,
+			target_format,
+			display: DisplaySettings::default(),
+			crosshair: false,
+			tile_stride,
+			frame_resources,
+		}
+	}
+
+	pub fn resize(&mut self, device: &Device, queue: &Queue, width: u32, height: u32) {
+		self.camera.width = width;
+		self.camera.height = height;
+		self.camera.frame = 0;
+		self.camera.update_buffer(queue);
+
+		self.frame_resources = create_frame_resources(
+			device,
+			&self.trace_bind_group_layout,
+			&self.blit_bind_group_layout,
+			&self.scene_buffers,
+			&self.blit_buffer,
+			&self.camera,
+			self.tile_stride,
+			width,
+			height,
+		);
+	}
+
+	pub fn tile_count(&self) -> u32 {
+		self.frame_resources.tiles_x * self.frame_resources.tiles_y
+	}
+
+	// Traces one full frame and advances the accumulation
+	pub fn trace(&mut self, encoder: &mut CommandEncoder) {
+		let FrameResources { width, height, .. } = self.frame_resources;
+		self.dispatch(encoder, [(0, width, height)]);
+		self.finish_frame();
+	}
+
+	// Traces part of the frame, tiles are numbered row-major starting at the top left.
+	// Once every tile has been traced, `finish_frame` must be called before the next frame.
+	pub fn trace_tiles(&self, encoder: &mut CommandEncoder, tiles: Range<u32>) {
+		self.dispatch(
+			encoder,
+			tiles
+				.filter(|&tile| tile < self.tile_count())
+				.map(|tile| (tile + 1, TILE_SIZE, TILE_SIZE)),
+		);
+	}
+
+	pub fn finish_frame(&mut self) {
+		self.camera.frame += 1;
+	}
+
+	fn dispatch(&self, encoder: &mut CommandEncoder, tiles: impl IntoIterator<Item = (u32, u32, u32)>) {
+		let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+			label: Some("Trace Pass"),
+			timestamp_writes: None,
+		});
+		compute_pass.set_pipeline(&self.trace_pipeline);
+
+		for (tile_index, width, height) in tiles {
+			compute_pass.set_bind_group(
+				0,
+				&self.frame_resources.trace_bind_group,
+				&[tile_index * self.tile_stride],
+			);
+			compute_pass.dispatch_workgroups(width.div_ceil(WORKGROUP_SIZE), height.div_ceil(WORKGROUP_SIZE), 1);
+		}
+	}
+
+	// Distance along the view direction to the surface in the middle of the image, using the last uploaded camera
+	pub fn focus_distance(&self, device: &Device, queue: &Queue) -> Option<f32> {
+		let readback_buffer = device.create_buffer(&BufferDescriptor {
+			label: Some("Focus Readback Buffer"),
+			size: size_of::<f32>() as BufferAddress,
+			usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		let mut encoder = device.create_command_encoder(&Default::default());
+		{
+			let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+				label: Some("Focus Pass"),
+				timestamp_writes: None,
+			});
+			compute_pass.set_pipeline(&self.focus_pipeline);
+			compute_pass.set_bind_group(0, &self.frame_resources.trace_bind_group, &[0]);
+			compute_pass.dispatch_workgroups(1, 1, 1);
+		}
+		encoder.copy_buffer_to_buffer(
+			&self.frame_resources.focus_buffer,
+			0,
+			&readback_buffer,
+			0,
+			size_of::<f32>() as BufferAddress,
+		);
+		queue.submit([encoder.finish()]);
+
+		let slice = readback_buffer.slice(..);
+		map_read(device, &slice).ok()?;
+		let distance = bytemuck::pod_read_unaligned::<f32>(&slice.get_mapped_range());
+		readback_buffer.unmap();
+
+		(distance > 0.0).then_some(distance)
+	}
+
+	// Draws a crosshair in the middle of the image when blitting, marking what `focus_distance` measures
+	pub fn set_crosshair(&mut self, queue: &Queue, visible: bool) {
+		self.crosshair = visible;
+		self.write_blit_uniform(queue);
+	}
+
+	pub fn display(&self) -> DisplaySettings {
+		self.display
+	}
+	// Only affects the blit, the accumulated image is kept
+	pub fn set_display(&mut self, queue: &Queue, display: DisplaySettings) {
+		self.display = display;
+		self.write_blit_uniform(queue);
+	}
+
+	fn write_blit_uniform(&self, queue: &Queue) {
+		let uniform = BlitUniform::new(self.display, self.crosshair, self.target_format);
+		queue.write_buffer(&self.blit_buffer, 0, bytemuck::bytes_of(&uniform));
+	}
+
+	// Tone maps the accumulated image into `view`, see `DisplaySettings`
+	pub fn blit(&self, encoder: &mut CommandEncoder, view: &TextureView) {
+		let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+			label: Some("Blit Pass"),
+			color_attachments: &[Some(RenderPassColorAttachment {
+				view,
+				resolve_target: None,
+				ops: Operations {
+					load: LoadOp::Clear(Color::GREEN),
+					store: StoreOp::Store,
+				},
+			})],
+			depth_stencil_attachment: None,
+			timestamp_writes: None,
+			occlusion_query_set: None,
+		});
+
+		render_pass.set_bind_group(0, &self.frame_resources.blit_bind_group, &[]);
+
+		render_pass.set_pipeline(&self.blit_pipeline);
+		render_pass.draw(0..3, 0..1);
+	}
+
+	pub fn render(&mut self, encoder: &mut CommandEncoder, view: &TextureView) {
+		self.trace(encoder);
+		self.blit(encoder, view);
+	}
+
+	// Blits the accumulated image without the crosshair and reads it back.
+	// None if the target format isn't 8-bit RGBA or BGRA.
+	pub fn capture(&self, device: &Device, queue: &Queue) -> Result<Image, CaptureError> {
+		let swizzle = match self.target_format.remove_srgb_suffix() {
+			TextureFormat::Rgba8Unorm => false,
+			TextureFormat::Bgra8Unorm => true,
+			_ => return Err(CaptureError::UnsupportedFormat(self.target_format)),
+		};
+		let FrameResources { width, height, .. } = self.frame_resources;
+
+		let target = device.create_texture(&TextureDescriptor {
+			label: Some("Capture Target"),
+			size: Extent3d {
+				width,
+				height,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: TextureDimension::D2,
+			format: self.target_format,
+			usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+			view_formats: &[],
+		});
+
+		let unpadded_bytes_per_row = width * 4;
+		let bytes_per_row = unpadded_bytes_per_row.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+
+		let readback_buffer = device.create_buffer(&BufferDescriptor {
+			label: Some("Capture Readback Buffer"),
+			size: bytes_per_row as BufferAddress * height as BufferAddress,
+			usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+			mapped_at_creation: false,
+		});
+
+		let uniform = BlitUniform::new(self.display, false, self.target_format);
+		queue.write_buffer(&self.blit_buffer, 0, bytemuck::bytes_of(&uniform));
+
+		let mut encoder = device.create_command_encoder(&Default::default());
+		self.blit(&mut encoder, &target.create_view(&Default::default()));
+		encoder.copy_texture_to_buffer(
+			TexelCopyTextureInfo {
+				texture: &target,
+				mip_level: 0,
+				origin: Origin3d::ZERO,
+				aspect: TextureAspect::All,
+			},
+			TexelCopyBufferInfo {
+				buffer: &readback_buffer,
+				layout: TexelCopyBufferLayout {
+					offset: 0,
+					bytes_per_row: Some(bytes_per_row),
+					rows_per_image: Some(height),
+				},
+			},
+			target.size(),
+		);
+		queue.submit([encoder.finish()]);
+		// Restores the crosshair for the next frame
+		self.write_blit_uniform(queue);
+
+		let slice = readback_buffer.slice(..);
+		map_read(device, &slice).map_err(CaptureError::Readback)?;
+
+		let mut pixels = Vec::with_capacity(unpadded_bytes_per_row as usize * height as usize);
+		{
+			let data = slice.get_mapped_range();
+			for row in data.chunks_exact(bytes_per_row as usize) {
+				pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+			}
+		}
+		readback_buffer.unmap();
+
+		if swizzle {
+			for pixel in pixels.chunks_exact_mut(4) {
+				pixel.swap(0, 2);
+			}
+		}
+		Ok(Image::new(width, height, pixels))
+	}
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+	UnsupportedFormat(TextureFormat),
+	Readback(String),
+}
+impl fmt::Display for CaptureError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CaptureError::UnsupportedFormat(format) => write!(f, "captures of {format:?} textures aren't supported"),
+			CaptureError::Readback(message) => write!(f, "failed to read the image back: {message}"),
+		}
+	}
+}
+
+// Blocks until the slice is mapped for reading
+fn map_read(device: &Device, slice: &BufferSlice) -> Result<(), String> {
+	let (sender, receiver) = mpsc::channel();
+	slice.map_async(MapMode::Read, move |result| {
+		let _ = sender.send(result);
+	});
+	device.poll(PollType::Wait).map_err(|e| e.to_string())?;
+	// The callback has run once the wait returns
+	match receiver.recv() {
+		Ok(result) => result.map_err(|e| e.to_string()),
+		Err(_) => Err("the buffer was never mapped".to_owned()),
+	}
+}
+
+// Storage bindings can't be empty, so empty slices get a single zeroed element
+fn storage_buffer_init<T: bytemuck::Pod>(device: &Device, label: &str, contents: &[T]) -> Buffer {
+	let zeroed = [T::zeroed()];
+	let contents = if contents.is_empty() { &zeroed[..] } else { contents };
+	device.create_buffer_init(&BufferInitDescriptor {
+		label: Some(label),
+		contents: bytemuck::cast_slice(contents),
+		usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+	})
+}
+
+// Texture arrays need layers of the same size, so every image is resized to the largest width and height. Unused layers
+// are white.
+fn create_image_array(device: &Device, queue: &Queue, images: &[RgbaImage]) -> TextureView {
+	let width = images.iter().map(RgbaImage::width).max().unwrap_or(1);
+	let height = images.iter().map(RgbaImage::height).max().unwrap_or(1);
+	let mut data = Vec::new();
+	for image in images {
+		if image.dimensions() == (width, height) {
+			data.extend_from_slice(image.as_raw());
+		} else {
+			data.extend_from_slice(imageops::resize(image, width, height, FilterType::Triangle).as_raw());
+		}
+	}
+	// The GL backend creates single layer textures as plain 2D textures, which can't be viewed as arrays
+	let layers = images.len().max(2);
+	data.resize(layers * width as usize * height as usize * 4, u8::MAX);
+
+	let texture = device.create_texture_with_data(
+		queue,
+		&TextureDescriptor {
+			label: Some("Image Array"),
+			size: Extent3d {
+				width,
+				height,
+				depth_or_array_layers: layers as u32,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: TextureDimension::D2,
+			format: TextureFormat::Rgba8UnormSrgb,
+			usage: TextureUsages::TEXTURE_BINDING,
+			view_formats: &[],
+		},
+		TextureDataOrder::LayerMajor,
+		&data,
+	);
+	texture.create_view(&TextureViewDescriptor {
+		label: Some("Image Array View"),
+		dimension: Some(TextureViewDimension::D2Array),
+		..Default::default()
+	})
+}
+
+// Sampled without filtering so lookups match the piecewise constant distribution, scenes without a map get a black texel
+fn create_environment_map(device: &Device, queue: &Queue, map: Option<&Rgba32FImage>) -> TextureView {
+	let black = Rgba32FImage::new(1, 1);
+	let map = map.unwrap_or(&black);
+	let texture = device.create_texture_with_data(
+		queue,
+		&TextureDescriptor {
+			label: Some("Environment Map"),
+			size: Extent3d {
+				width: map.width(),
+				height: map.height(),
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: TextureDimension::D2,
+			format: TextureFormat::Rgba32Float,
+			usage: TextureUsages::TEXTURE_BINDING,
+			view_formats: &[],
+		},
+		TextureDataOrder::LayerMajor,
+		bytemuck::cast_slice(map.as_raw()),
+	);
+	texture.create_view(&Default::default())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn create_frame_resources(
+	device: &Device,
+	trace_bind_group_layout: &BindGroupLayout,
+	blit_bind_group_layout: &BindGroupLayout,
+	scene_buffers: &SceneBuffers,
+	blit_buffer: &Buffer,
+	camera: &Camera,
+	tile_stride: u32,
+	width: u32,
+	height: u32,
+) -> FrameResources {
+	let width = width.max(1);
+	let height = height.max(1);
+
+	let accumulation_buffer = device.create_buffer(&BufferDescriptor {
+		label: Some("Accumulation Buffer"),
+		size: width as BufferAddress * height as BufferAddress * size_of::<[f32; 4]>() as BufferAddress,
+		usage: BufferUsages::STORAGE,
+		mapped_at_creation: false,
+	});
+
+	let output_texture = device.create_texture(&TextureDescriptor {
+		label: Some("Trace Output Texture"),
+		size: Extent3d {
+			width,
+			height,
+			depth_or_array_layers: 1,
+		},
+		mip_level_count: 1,
+		sample_count: 1,
+		dimension: TextureDimension::D2,
+		format: OUTPUT_FORMAT,
+		usage: TextureUsages::STORAGE_BINDING | TextureUsages::TEXTURE_BINDING,
+		view_formats: &[],
+	});
+	let output_view = output_texture.create_view(&Default::default());
+
+	// Entry 0 covers the whole image, the rest are the tiles in row-major order
+	let tiles_x = width.div_ceil(TILE_SIZE);
+	let tiles_y = height.div_ceil(TILE_SIZE);
+	let mut tile_data = vec![0u8; (tile_stride * (tiles_x * tiles_y + 1)) as usize];
+	let tiles = std::iter::once(TileUniform {
+		origin: [0, 0],
+		size: [width, height],
+	})
+	.chain((0..tiles_y).flat_map(|y| {
+		(0..tiles_x).map(move |x| TileUniform {
+			origin: [x * TILE_SIZE, y * TILE_SIZE],
+			size: [TILE_SIZE, TILE_SIZE],
+		})
+	}));
+	for (chunk, tile) in tile_data.chunks_exact_mut(tile_stride as usize).zip(tiles) {
+		chunk[..size_of::<TileUniform>()].copy_from_slice(bytemuck::bytes_of(&tile));
+	}
+	let tile_buffer = device.create_buffer_init(&BufferInitDescriptor {
+		label: Some("Tile Buffer"),
+		contents: &tile_data,
+		usage: BufferUsages::UNIFORM,
+	});
+
+	let focus_buffer = device.create_buffer(&BufferDescriptor {
+		label: Some("Focus Buffer"),
+		size: size_of::<f32>() as BufferAddress,
+		usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+		mapped_at_creation: false,
+	});
+
+	let trace_bind_group = device.create_bind_group(&BindGroupDescriptor {
+		layout: trace_bind_group_layout,
+		entries: &[
+			BindGroupEntry {
+				binding: 0,
+				resource: camera.binding(),
+			},
+			BindGroupEntry {
+				binding: 1,
+				resource: scene_buffers.spheres.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 2,
+				resource: scene_buffers.materials.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 3,
+				resource: accumulation_buffer.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 4,
+				resource: scene_buffers.bvh_nodes.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 5,
+				resource: BindingResource::TextureView(&output_view),
+			},
+			BindGroupEntry {
+				binding: 6,
+				resource: BindingResource::Buffer(BufferBinding {
+					buffer: &tile_buffer,
+					offset: 0,
+					size: BufferSize::new(size_of::<TileUniform>() as u64),
+				}),
+			},
+			BindGroupEntry {
+				binding: 7,
+				resource: scene_buffers.background.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 8,
+				resource: scene_buffers.lights.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 9,
+				resource: scene_buffers.vertices.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 10,
+				resource: scene_buffers.triangles.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 11,
+				resource: scene_buffers.primitives.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 12,
+				resource: scene_buffers.quads.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 13,
+				resource: scene_buffers.planes.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 14,
+				resource: scene_buffers.disks.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 15,
+				resource: scene_buffers.instances.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 16,
+				resource: scene_buffers.textures.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 17,
+				resource: BindingResource::TextureView(&scene_buffers.images),
+			},
+			BindGroupEntry {
+				binding: 18,
+				resource: BindingResource::Sampler(&scene_buffers.image_sampler),
+			},
+			BindGroupEntry {
+				binding: 19,
+				resource: BindingResource::TextureView(&scene_buffers.environment_map),
+			},
+			BindGroupEntry {
+				binding: 20,
+				resource: scene_buffers.environment_distribution.as_entire_binding(),
+			},
+			BindGroupEntry {
+				binding: 21,
+				resource: focus_buffer.as_entire_binding(),
+			},
+		],
+		label: Some("Trace Bind Group"),
+	});
+
+	let blit_bind_group = device.create_bind_group(&BindGroupDescriptor {
+		layout: blit_bind_group_layout,
+		entries: &[
+			BindGroupEntry {
+				binding: 0,
+				resource: BindingResource::TextureView(&output_view),
+			},
+			BindGroupEntry {
+				binding: 1,
+				resource: blit_buffer.as_entire_binding(),
+			},
+		],
+		label: Some("Blit Bind Group"),
+	});
+
+	FrameResources {
+		width,
+		height,
+		tiles_x,
+		tiles_y,
+		_accumulation_buffer: accumulation_buffer,
+		_output_texture: output_texture,
+		_tile_buffer: tile_buffer,
+		focus_buffer,
+		trace_bind_group,
+		blit_bind_group,
+	}
+}
@@ -0,0 +1,149 @@
+// Checks that the structs uploaded by the tracer have the layout the shaders declare for their bindings. The WGSL
+// struct names are mangled by the linker, so they are looked up through the binding instead.
+
+use super::{BlitUniform, TileUniform};
+use crate::background::BackgroundUniform;
+use crate::bvh::BvhNode;
+use crate::camera::CameraUniform;
+use crate::disk::Disk;
+use crate::instance::Instance;
+use crate::layout::Primitive;
+use crate::material::Material;
+use crate::mesh::{Triangle, Vertex};
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::sphere::Sphere;
+use crate::texture::Texture;
+use naga::proc::Layouter;
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga::{AddressSpace, Handle, Module, Type, TypeInner};
+use std::mem::{offset_of, size_of};
+use wesl::include_wesl;
+
+struct Shader {
+	module: Module,
+	layouter: Layouter,
+}
+impl Shader {
+	fn parse(source: &str) -> Self {
+		let module = naga::front::wgsl::parse_str(source).unwrap();
+		Validator::new(ValidationFlags::all(), Capabilities::all()).validate(&module).unwrap();
+		let mut layouter = Layouter::default();
+		layouter.update(module.to_ctx()).unwrap();
+		Self { module, layouter }
+	}
+
+	// Type of the variable at `binding` in group 0, the element type for arrays
+	fn bound_type(&self, binding: u32) -> (Handle<Type>, AddressSpace) {
+		let (_, variable) = self
+			.module
+			.global_variables
+			.iter()
+			.find(|(_, variable)| variable.binding.as_ref().is_some_and(|b| b.group == 0 && b.binding == binding))
+			.unwrap_or_else(|| panic!("nothing bound at binding {binding}"));
+		match self.module.types[variable.ty].inner {
+			TypeInner::Array { base, stride, .. } => {
+				assert_eq!(stride, self.layouter[base].size, "stride of the array at binding {binding}");
+				(base, variable.space)
+			},
+			_ => (variable.ty, variable.space),
+		}
+	}
+
+	// `fields` are the Rust offsets of the members with the same names, every member has to be listed
+	fn assert_layout(&self, binding: u32, rust_name: &str, size: usize, fields: &[(&str, usize)]) {
+		let (ty, space) = self.bound_type(binding);
+		let TypeInner::Struct { ref members, span } = self.module.types[ty].inner else {
+			panic!("binding {binding} isn't a struct");
+		};
+		// Uniforms may be padded to whole 16 byte rows, array elements have to match the stride exactly
+		let span = match space {
+			AddressSpace::Uniform => span.next_multiple_of(16),
+			_ => span,
+		};
+		assert_eq!(span as usize, size, "size of {rust_name}");
+		let names = members.iter().map(|member| member.name.as_deref().unwrap_or_default()).collect::<Vec<_>>();
+		let rust_names = fields.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+		assert_eq!(names, rust_names, "fields of {rust_name}");
+		for (member, (name, offset)) in members.iter().zip(fields) {
+			assert_eq!(member.offset as usize, *offset, "offset of {rust_name}::{name}");
+		}
+	}
+}
+
+macro_rules! assert_layout {
+	($shader:expr, $binding:expr, $type:ty { $($field:ident),* $(,)? }) => {
+		$shader.assert_layout(
+			$binding,
+			stringify!($type),
+			size_of::<$type>(),
+			&[$((stringify!($field), offset_of!($type, $field))),*],
+		)
+	};
+}
+
+// Must match the bindings in trace.wesl
+#[test]
+fn trace_bindings() {
+	let shader = Shader::parse(include_wesl!("trace"));
+
+	assert_layout!(shader, 0, CameraUniform {
+		center,
+		samples_per_pixel,
+		pixel00_loc,
+		max_depth,
+		pixel_delta_u,
+		frame,
+		pixel_delta_v,
+		width,
+		defocus_disk_u,
+		height,
+		defocus_disk_v,
+		shutter_open,
+		shutter_close,
+		seed,
+	});
+	assert_layout!(shader, 1, Sphere { center, radius, motion, material });
+	assert_layout!(shader, 2, Material { data, parameters, material_type, texture });
+	assert_layout!(shader, 4, BvhNode { min, left_or_first, max, count });
+	assert_layout!(shader, 6, TileUniform { origin, size });
+	assert_layout!(shader, 7, BackgroundUniform {
+		color,
+		background_type,
+		rotation,
+		intensity,
+		fog_density,
+		fog_material,
+	});
+	assert_layout!(shader, 9, Vertex { position, u, normal, v });
+	assert_layout!(shader, 10, Triangle { vertices, material });
+	assert_layout!(shader, 11, Primitive { kind, index });
+	assert_layout!(shader, 12, Quad { q, material, u, v });
+	assert_layout!(shader, 13, Plane { point, material, normal });
+	assert_layout!(shader, 14, Disk { center, radius, normal, material });
+	assert_layout!(shader, 15, Instance {
+		object_to_world,
+		world_to_object,
+		motion,
+		object,
+		root,
+		density,
+		material,
+	});
+	assert_layout!(shader, 16, Texture { color, scale, odd, texture_type, layer });
+
+	// The light buffer is a plain list of u32, see `light_data` in `Tracer::new`
+	let (lights, _) = shader.bound_type(8);
+	shader.assert_layout(8, "light_data", shader.layouter[lights].size as usize, &[
+		("count", 0),
+		("indices", size_of::<u32>()),
+	]);
+}
+
+// Must match the bindings in blit.wesl
+#[test]
+fn blit_bindings() {
+	let shader = Shader::parse(include_wesl!("blit"));
+
+	assert_layout!(shader, 1, BlitUniform { exposure, tone_mapper, dither, crosshair, encode_srgb });
+}
@@ -1,15 +1,67 @@
+use crate::background::{Background, SurfaceFog};
+use crate::disk::Disk;
+use crate::environment;
+use crate::instance::{Instance, ObjectReference};
 use crate::material::{Material, MaterialReference};
+use crate::mesh::{Triangle, Vertex};
+use crate::obj::{self, ObjError};
+use crate::plane::Plane;
+use crate::quad::{self, Quad};
 use crate::sphere::Sphere;
+use crate::texture::{Texture, TextureReference};
+use glam::{Affine3A, Mat4, Vec3};
+use image::{ImageError, Rgba32FImage, RgbaImage};
+use std::ops::Range;
+use std::path::Path;
+
+// Geometry that is only placed in the world through instances, as ranges of the primitives in `World`
+#[derive(Debug, Clone)]
+pub struct Object {
+	pub spheres: Range<usize>,
+	pub triangles: Range<usize>,
+	pub quads: Range<usize>,
+	pub disks: Range<usize>,
+}
 
 pub struct World {
 	materials: Vec<Material>,
+	textures: Vec<Texture>,
+	// Layers of the image array, sRGB encoded
+	images: Vec<RgbaImage>,
 	spheres: Vec<Sphere>,
+	vertices: Vec<Vertex>,
+	triangles: Vec<Triangle>,
+	quads: Vec<Quad>,
+	planes: Vec<Plane>,
+	disks: Vec<Disk>,
+	objects: Vec<Object>,
+	instances: Vec<Instance>,
+	background: Background,
+	environment_map: Option<Rgba32FImage>,
+	surface_fog: Option<SurfaceFog>,
+}
+impl Default for World {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 impl World {
 	pub fn new() -> Self {
 		Self {
 			materials: Vec::new(),
+			textures: Vec::new(),
+			images: Vec::new(),
 			spheres: Vec::new(),
+			vertices: Vec::new(),
+			triangles: Vec::new(),
+			quads: Vec::new(),
+			planes: Vec::new(),
+			disks: Vec::new(),
+			objects: Vec::new(),
+			instances: Vec::new(),
+			background: Background::default(),
+			environment_map: None,
+			surface_fog: None,
 		}
 	}
 	pub fn add_material(&mut self, material: Material) -> MaterialReference {
@@ -17,13 +69,175 @@ impl World {
 		self.materials.push(material);
 		mr
 	}
+	pub fn add_texture(&mut self, texture: Texture) -> TextureReference {
+		let tr = TextureReference::new(u32::try_from(self.textures.len()).unwrap());
+		self.textures.push(texture);
+		tr
+	}
+	// Adds `image` as a new layer of the image array, returns the layer for `Texture::image`
+	pub fn add_image(&mut self, image: RgbaImage) -> u32 {
+		self.images.push(image);
+		u32::try_from(self.images.len() - 1).unwrap()
+	}
+	pub fn load_image(&mut self, path: &Path) -> Result<u32, ImageError> {
+		let image = image::open(path)?.into_rgba8();
+		Ok(self.add_image(image))
+	}
 	pub fn add_sphere(&mut self, sphere: Sphere) {
 		self.spheres.push(sphere);
 	}
+	pub fn add_quad(&mut self, quad: Quad) {
+		self.quads.push(quad);
+	}
+	pub fn add_plane(&mut self, plane: Plane) {
+		self.planes.push(plane);
+	}
+	pub fn add_disk(&mut self, disk: Disk) {
+		self.disks.push(disk);
+	}
+	// Axis-aligned box with opposite corners `a` and `b`
+	pub fn add_box(&mut self, a: Vec3, b: Vec3, material: MaterialReference) {
+		self.quads.extend(quad::box_sides(a, b, material));
+	}
+	// `indices` refer to `vertices`
+	pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[[u32; 3]], material: MaterialReference) {
+		let offset = u32::try_from(self.vertices.len()).unwrap();
+		self.vertices.extend_from_slice(vertices);
+		self.triangles.extend(
+			indices
+				.iter()
+				.map(|face| Triangle::new(face.map(|i| offset + i), material)),
+		);
+	}
+	// Meshes without a material in the .mtl file use `default_material`
+	pub fn load_obj(
+		&mut self,
+		path: &Path,
+		transform: Mat4,
+		default_material: MaterialReference,
+	) -> Result<(), ObjError> {
+		obj::load(self, path, transform, default_material)
+	}
+	// Everything `build` adds becomes part of the object instead of the world
+	pub fn add_object(&mut self, build: impl FnOnce(&mut ObjectBuilder)) -> ObjectReference {
+		let start = [
+			self.spheres.len(),
+			self.triangles.len(),
+			self.quads.len(),
+			self.disks.len(),
+		];
+		build(&mut ObjectBuilder { world: self });
+
+		self.objects.push(Object {
+			spheres: start[0]..self.spheres.len(),
+			triangles: start[1]..self.triangles.len(),
+			quads: start[2]..self.quads.len(),
+			disks: start[3]..self.disks.len(),
+		});
+		ObjectReference::new(u32::try_from(self.objects.len() - 1).unwrap())
+	}
+	pub fn add_instance(&mut self, object: ObjectReference, transform: Affine3A) {
+		self.instances.push(Instance::new(object, transform));
+	}
+	// Translates the instance by `motion` between time 0 and 1
+	pub fn add_moving_instance(&mut self, object: ObjectReference, transform: Affine3A, motion: Vec3) {
+		self.instances.push(Instance::new(object, transform).with_motion(motion));
+	}
+	// Fills the closed, convex `boundary` with a medium that scatters according to `material`, usually isotropic
+	pub fn add_medium(
+		&mut self,
+		boundary: ObjectReference,
+		transform: Affine3A,
+		density: f32,
+		material: MaterialReference,
+	) {
+		self.instances.push(Instance::new(boundary, transform).with_medium(density, material));
+	}
+	pub fn set_background(&mut self, background: Background) {
+		self.background = background;
+	}
+	// Used by `Background::Environment`
+	pub fn set_environment_map(&mut self, map: Rgba32FImage) {
+		self.environment_map = Some(map);
+	}
+	pub fn load_environment_map(&mut self, path: &Path) -> Result<(), ImageError> {
+		self.set_environment_map(environment::load(path)?);
+		Ok(())
+	}
+	pub fn set_surface_fog(&mut self, fog: SurfaceFog) {
+		self.surface_fog = Some(fog);
+	}
 	pub fn materials(&self) -> &[Material] {
 		&self.materials
 	}
+	pub fn textures(&self) -> &[Texture] {
+		&self.textures
+	}
+	pub fn images(&self) -> &[RgbaImage] {
+		&self.images
+	}
 	pub fn spheres(&self) -> &[Sphere] {
 		&self.spheres
 	}
+	pub fn vertices(&self) -> &[Vertex] {
+		&self.vertices
+	}
+	pub fn triangles(&self) -> &[Triangle] {
+		&self.triangles
+	}
+	pub fn quads(&self) -> &[Quad] {
+		&self.quads
+	}
+	pub fn planes(&self) -> &[Plane] {
+		&self.planes
+	}
+	pub fn disks(&self) -> &[Disk] {
+		&self.disks
+	}
+	pub fn objects(&self) -> &[Object] {
+		&self.objects
+	}
+	pub fn instances(&self) -> &[Instance] {
+		&self.instances
+	}
+	pub fn background(&self) -> Background {
+		self.background
+	}
+	pub fn environment_map(&self) -> Option<&Rgba32FImage> {
+		self.environment_map.as_ref()
+	}
+	pub fn surface_fog(&self) -> Option<SurfaceFog> {
+		self.surface_fog
+	}
+}
+
+// Adds the primitives of an object, planes are unbounded and objects can't nest, so neither can be added
+pub struct ObjectBuilder<'a> {
+	world: &'a mut World,
+}
+impl ObjectBuilder<'_> {
+	pub fn add_sphere(&mut self, sphere: Sphere) {
+		self.world.add_sphere(sphere);
+	}
+	pub fn add_quad(&mut self, quad: Quad) {
+		self.world.add_quad(quad);
+	}
+	pub fn add_disk(&mut self, disk: Disk) {
+		self.world.add_disk(disk);
+	}
+	pub fn add_box(&mut self, a: Vec3, b: Vec3, material: MaterialReference) {
+		self.world.add_box(a, b, material);
+	}
+	pub fn add_mesh(&mut self, vertices: &[Vertex], indices: &[[u32; 3]], material: MaterialReference) {
+		self.world.add_mesh(vertices, indices, material);
+	}
+	// Materials and textures of the .mtl file are added to the world
+	pub fn load_obj(
+		&mut self,
+		path: &Path,
+		transform: Mat4,
+		default_material: MaterialReference,
+	) -> Result<(), ObjError> {
+		self.world.load_obj(path, transform, default_material)
+	}
 }
@@ -0,0 +1,213 @@
+// Renders canonical scenes on the software adapter and compares them against the reference images in tests/golden.
+// Run with UPDATE_GOLDEN=1 to rewrite the references after an intended change to the output. On failure the actual and
+// diff images are written to target/tmp/golden.
+
+use image::{Rgb, RgbImage, RgbaImage};
+use rtiow_wgpu::display::DisplaySettings;
+use rtiow_wgpu::gpu::AdapterOptions;
+use rtiow_wgpu::scene_file;
+use rtiow_wgpu::scenes;
+use rtiow_wgpu::plane::Plane;
+use rtiow_wgpu::{CameraParameters, CpuRenderer, Image, Material, RenderSettings, Renderer, World};
+use glam::vec3;
+use std::path::{Path, PathBuf};
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 64;
+const SAMPLES_PER_PIXEL: u32 = 8;
+const SAMPLES: u32 = 32;
+
+// Root mean square error in 8-bit steps over all channels. The cases all use the default seed, so the same adapter
+// reproduces an image exactly and the tolerance only covers other drivers rounding differently.
+const TOLERANCE: f32 = 1.0;
+// Scattering in media branches on every random number, so paths diverge at the first rounding difference
+const MEDIUM_TOLERANCE: f32 = 2.5;
+// The CPU renderer matches the GPU path for path until rounding makes them diverge
+const CPU_TOLERANCE: f32 = 4.0;
+
+struct Case {
+	name: &'static str,
+	// Built-in scene name or a scene file relative to the crate root
+	scene: &'static str,
+	tolerance: f32,
+}
+
+const CASES: &[Case] = &[
+	Case { name: "random-spheres", scene: "random-spheres", tolerance: TOLERANCE },
+	Case { name: "bouncing-spheres", scene: "bouncing-spheres", tolerance: TOLERANCE },
+	Case { name: "materials", scene: "materials", tolerance: TOLERANCE },
+	Case { name: "sphere-light", scene: "sphere-light", tolerance: TOLERANCE },
+	Case { name: "cornell-box", scene: "cornell-box", tolerance: TOLERANCE },
+	Case { name: "cornell-smoke", scene: "cornell-smoke", tolerance: MEDIUM_TOLERANCE },
+	Case { name: "checkered-spheres", scene: "checkered-spheres", tolerance: TOLERANCE },
+	Case { name: "perlin-spheres", scene: "perlin-spheres", tolerance: TOLERANCE },
+	Case { name: "microfacet-spheres", scene: "microfacet-spheres", tolerance: TOLERANCE },
+	Case { name: "mesh-file", scene: "scenes/mesh.toml", tolerance: TOLERANCE },
+	Case { name: "textures-file", scene: "scenes/textures.toml", tolerance: TOLERANCE },
+	Case { name: "volumes-file", scene: "scenes/volumes.toml", tolerance: MEDIUM_TOLERANCE },
+];
+
+// Scenes where the CPU renderer is compared against the GPU instead of a reference image
+const CPU_CASES: &[&str] = &["sphere-light", "checkered-spheres", "microfacet-spheres", "scenes/mesh.toml"];
+
+#[test]
+fn golden_images() {
+	let renderer = software_renderer();
+	let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+
+	let mut failures = Vec::new();
+	for case in CASES {
+		let (world, camera_parameters, display) = load(case.scene, 0);
+		let image = renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap();
+		let reference_path = golden_dir().join(format!("{}.png", case.name));
+
+		if update {
+			image.save(&reference_path).unwrap();
+			continue;
+		}
+		let Ok(reference) = image::open(&reference_path) else {
+			failures.push(format!("{}: missing {}, run with UPDATE_GOLDEN=1", case.name, reference_path.display()));
+			continue;
+		};
+		let reference = reference.into_rgba8();
+		if let Some(failure) = compare(case.name, &image, &reference, case.tolerance) {
+			failures.push(failure);
+		}
+	}
+	assert!(failures.is_empty(), "golden images differ:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn cpu_matches_gpu() {
+	let renderer = software_renderer();
+	let cpu_renderer = CpuRenderer::new();
+
+	let mut failures = Vec::new();
+	for scene in CPU_CASES {
+		let (world, camera_parameters, display) = load(scene, 0);
+		let gpu_image = renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap();
+		let cpu_image = cpu_renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap();
+		let name = format!("cpu-{}", Path::new(scene).file_stem().unwrap().to_string_lossy());
+		let gpu_image = RgbaImage::from_raw(gpu_image.width, gpu_image.height, gpu_image.pixels).unwrap();
+		if let Some(failure) = compare(&name, &cpu_image, &gpu_image, CPU_TOLERANCE) {
+			failures.push(failure);
+		}
+	}
+	assert!(failures.is_empty(), "CPU and GPU renders differ:\n{}", failures.join("\n"));
+}
+
+// Worlds without a BVH over their primitives, planes are intersected separately
+#[test]
+fn worlds_without_primitives() {
+	let renderer = software_renderer();
+	let cpu_renderer = CpuRenderer::new();
+	let (_, camera_parameters, display) = load("random-spheres", 0);
+
+	let mut plane_world = World::new();
+	let material = plane_world.add_material(Material::lambertian(vec3(0.5, 0.5, 0.5)));
+	plane_world.add_plane(Plane::new(vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0), material));
+
+	let mut failures = Vec::new();
+	for (name, world) in [("empty", World::new()), ("plane-only", plane_world)] {
+		let gpu_image = renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap();
+		let cpu_image = cpu_renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap();
+		let gpu_image = RgbaImage::from_raw(gpu_image.width, gpu_image.height, gpu_image.pixels).unwrap();
+		if let Some(failure) = compare(name, &cpu_image, &gpu_image, CPU_TOLERANCE) {
+			failures.push(failure);
+		}
+	}
+	assert!(failures.is_empty(), "CPU and GPU renders differ:\n{}", failures.join("\n"));
+}
+
+// The seed drives both the scene generator and the per pixel random numbers
+#[test]
+fn seeds_reproduce_images() {
+	let renderer = software_renderer();
+	let render = |seed| {
+		let (world, camera_parameters, display) = load("random-spheres", seed);
+		renderer.render_to_image(&world, &camera_parameters, settings(display)).unwrap().pixels
+	};
+
+	let image = render(7);
+	assert!(image == render(7), "the same seed rendered different images");
+	assert!(image != render(8), "different seeds rendered the same image");
+}
+
+// The references are rendered on a software adapter such as llvmpipe, lavapipe or WARP, the tests fail without one
+fn software_renderer() -> Renderer {
+	let adapter_options = AdapterOptions {
+		force_fallback_adapter: true,
+		..Default::default()
+	};
+	pollster::block_on(Renderer::new(&adapter_options)).unwrap_or_else(|e| panic!("no software adapter: {e}"))
+}
+
+// Seed 0 is the default of the command line
+fn load(scene: &str, seed: u32) -> (World, CameraParameters, DisplaySettings) {
+	let (world, mut camera_parameters, display) = match scenes::by_name(scene, seed) {
+		Some((world, camera_parameters)) => (world, camera_parameters, DisplaySettings::default()),
+		None => {
+			let path = Path::new(env!("CARGO_MANIFEST_DIR")).join(scene);
+			let scene_file = scene_file::load(&path).unwrap_or_else(|e| panic!("{e}"));
+			(scene_file.world, scene_file.camera_parameters, scene_file.display)
+		},
+	};
+	camera_parameters.samples_per_pixel = SAMPLES_PER_PIXEL;
+	camera_parameters.seed = seed;
+	(world, camera_parameters, display)
+}
+
+fn settings(display: DisplaySettings) -> RenderSettings {
+	RenderSettings {
+		width: WIDTH,
+		height: HEIGHT,
+		samples: SAMPLES,
+		display,
+	}
+}
+
+fn golden_dir() -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden")
+}
+
+// Writes the actual image and a diff and describes the failure if the error exceeds the tolerance
+fn compare(name: &str, image: &Image, reference: &RgbaImage, tolerance: f32) -> Option<String> {
+	if reference.dimensions() != (image.width, image.height) {
+		return Some(format!(
+			"{name}: reference is {}x{}, rendered {}x{}",
+			reference.width(),
+			reference.height(),
+			image.width,
+			image.height
+		));
+	}
+
+	let mut squared_error = 0.0;
+	// Absolute differences scaled up so single steps are visible
+	let mut diff = RgbImage::new(image.width, image.height);
+	for ((actual, expected), diff) in image.pixels.chunks_exact(4).zip(reference.pixels()).zip(diff.pixels_mut()) {
+		let mut channels = [0; 3];
+		for (channel, difference) in channels.iter_mut().enumerate() {
+			let d = actual[channel].abs_diff(expected.0[channel]);
+			squared_error += (d as f64).powi(2);
+			*difference = d.saturating_mul(8);
+		}
+		*diff = Rgb(channels);
+	}
+	let rmse = (squared_error / (image.pixels.len() / 4 * 3) as f64).sqrt() as f32;
+	if rmse <= tolerance {
+		return None;
+	}
+
+	let output_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+	std::fs::create_dir_all(&output_dir).unwrap();
+	let actual_path = output_dir.join(format!("{name}-actual.png"));
+	let diff_path = output_dir.join(format!("{name}-diff.png"));
+	image.save(&actual_path).unwrap();
+	diff.save(&diff_path).unwrap();
+	Some(format!(
+		"{name}: RMSE {rmse:.2} exceeds {tolerance}, see {} and {}",
+		actual_path.display(),
+		diff_path.display()
+	))
+}